@@ -1,6 +1,9 @@
-use std::io::{Read, Seek, Write};
+use std::{cell::{Cell, Ref, RefCell}, collections::HashMap, path::{Path, PathBuf}};
 
-use crate::{app::undotree::UndoTree, Cursor};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::Direction;
+
+use crate::{app::{colorscheme::{ColorDepth, Colorscheme}, commandline::{CommandLine, CompletionSources, LineEditor}, fileio::{self, DiskState, TextFormat}, explorer::Explorer, hex::HexView, largefile::LargeFile, options::{split_args, LocalOptions, OptionValue, Options}, prompt::Prompt, render::LineIndex, signs::Signs, undotree::UndoTree, usercommand::UserCommand, window::{Window, WindowTree}}, Cursor};
 
 pub enum CurrentScreenMode {
     Main,
@@ -9,28 +12,172 @@ pub enum CurrentScreenMode {
 }
 
 pub struct File {
-    handle: std::fs::File,
     pub name: String,
     pub undo_tree: UndoTree,
+    pub local_options: LocalOptions,
+    pub disk_state: Option<DiskState>,
+    pub large: Option<LargeFile>,
+    pub hex: Option<HexView>,
+    pub explorer: Option<Explorer>,
+    pub swap: Option<PathBuf>,
+    pub swap_hash: Option<u64>,
+    pub new_file: bool,
+    pub scratch: bool,
+    pub signs: Signs,
     saved_state: String,
+    line_index: RefCell<LineIndex>,
+    modified: Cell<Option<(u64, bool)>>,
 }
 
 impl File {
-    pub fn new(handle: std::fs::File, name: String) -> File {
+    pub fn new(name: String) -> File {
         File {
-            handle,
             name,
             undo_tree: UndoTree::new(),
+            local_options: LocalOptions::new(),
+            disk_state: None,
+            large: None,
+            hex: None,
+            explorer: None,
+            swap: None,
+            swap_hash: None,
+            new_file: false,
+            scratch: false,
+            signs: Signs::default(),
             saved_state: String::new(),
+            line_index: RefCell::new(LineIndex::default()),
+            modified: Cell::new(None),
+        }
+    }
+
+    pub fn set_saved(&mut self, text: String) {
+        self.saved_state = text;
+        self.modified.set(None);
+        self.disk_state = fileio::disk_state(Path::new(&self.name));
+    }
+
+    pub fn changed_on_disk(&self) -> bool {
+        self.disk_state.is_some() && fileio::disk_state(Path::new(&self.name)) != self.disk_state
+    }
+
+    pub fn text(&self) -> String {
+        match self.undo_tree.show_current_node() {
+            Some((text, _)) => text,
+            None => String::new(),
+        }
+    }
+
+    pub fn is_modified(&self) -> bool {
+        if self.scratch {
+            return false;
+        }
+        if let Some(hex) = &self.hex {
+            return hex.modified;
+        }
+        if let Some(large) = &self.large {
+            return large.modified;
+        }
+        let version = self.undo_tree.version();
+        if let Some((checked, modified)) = self.modified.get() && checked == version {
+            return modified;
+        }
+        let modified = match &self.undo_tree.current {
+            Some(node) => node.borrow().text != self.saved_state,
+            None => !self.saved_state.is_empty(),
+        };
+        self.modified.set(Some((version, modified)));
+        modified
+    }
+
+    pub fn lines(&self) -> Option<(Ref<'_, str>, usize, Ref<'_, LineIndex>)> {
+        let (text, cursor) = self.undo_tree.current_text()?;
+        self.line_index.borrow_mut().update(&text, self.undo_tree.version());
+        Some((text, cursor, self.line_index.borrow()))
+    }
+
+    pub fn set_format(&mut self, format: TextFormat) {
+        self.local_options.insert("fileencoding", OptionValue::String(format.encoding));
+        self.local_options.insert("fileformat", OptionValue::String(format.fileformat));
+        self.local_options.insert("bomb", OptionValue::Bool(format.bomb));
+        self.local_options.insert("endofline", OptionValue::Bool(format.endofline));
+    }
+
+    pub fn format(&self, options: &Options) -> TextFormat {
+        let local = Some(&self.local_options);
+        TextFormat {
+            encoding: options.string(local, "fileencoding").to_owned(),
+            fileformat: options.string(local, "fileformat").to_owned(),
+            bomb: options.bool(local, "bomb"),
+            endofline: options.bool(local, "endofline") || options.bool(local, "fixendofline"),
         }
     }
+
+    pub fn write(&mut self, options: &Options) -> std::io::Result<String> {
+        if let Some(hex) = &mut self.hex {
+            fileio::write_atomic(Path::new(&self.name), &hex.bytes)?;
+            hex.modified = false;
+            let message = format!("\"{}\" {}B written", self.name, hex.bytes.len());
+            self.new_file = false;
+            self.set_saved(String::new());
+            return Ok(message);
+        }
+        if let Some(large) = &mut self.large {
+            let (lines, bytes) = large.save(Path::new(&self.name))?;
+            large.modified = false;
+            let message = format!("\"{}\" {}L, {}B written", self.name, lines, bytes);
+            self.set_saved(String::new());
+            return Ok(message);
+        }
+        let text = self.text();
+        let format = self.format(options);
+        let data = fileio::encode_text(&text, &format)?;
+        fileio::write_atomic(Path::new(&self.name), &data)?;
+        let message = written_message(&self.name, &text, &data, &format);
+        self.local_options.insert("endofline", OptionValue::Bool(format.endofline));
+        self.new_file = false;
+        self.set_saved(text);
+        Ok(message)
+    }
+}
+
+fn write_error(name: &str, error: std::io::Error) -> String {
+    match error.kind() {
+        std::io::ErrorKind::InvalidData => error.to_string(),
+        _ => format!("E212: Can't open file for writing: \"{}\" {}", name, error),
+    }
+}
+
+fn written_message(name: &str, text: &str, data: &[u8], format: &TextFormat) -> String {
+    let mut flags = String::new();
+    if format.encoding != "utf-8" {
+        flags.push_str(&format!("[{}]", format.encoding));
+    }
+    if format.fileformat != "unix" {
+        flags.push_str(&format!("[{}]", format.fileformat));
+    }
+    if !format.endofline && !text.is_empty() {
+        flags.push_str("[noeol]");
+    }
+    if format.bomb {
+        flags.push_str("[BOM]");
+    }
+    let lines = if text.is_empty() && !format.endofline { 0 } else { text.split('\n').count() };
+    format!("\"{}\" {}{}L, {}B written", name, if flags.is_empty() { flags } else { flags + " " }, lines, data.len())
+}
+
+pub struct Message {
+    pub text: String,
+    pub error: bool,
 }
 
 pub enum CurrentEditing {
     Page,
-    Command(String),
+    Replace,
+    Command(LineEditor),
+    Search(LineEditor),
     Selecting,
     Listening(char),
+    Prompt(Prompt),
 }
 
 pub struct App {
@@ -38,6 +185,19 @@ pub struct App {
     pub current_screen: CurrentScreenMode,
     pub current_editing: CurrentEditing,
     yanke: String,
+    pub command_line: CommandLine,
+    pub last_search: String,
+    pub options: Options,
+    pub message: Option<Message>,
+    pub messages: Vec<Message>,
+    pub show_messages: bool,
+    pub user_commands: Vec<UserCommand>,
+    pub aliases: HashMap<String, String>,
+    pub recover: bool,
+    pub windows: Vec<Window>,
+    pub window_tree: WindowTree,
+    pub current_window: usize,
+    pub colors: Colorscheme,
 }
 
 impl App {
@@ -47,45 +207,379 @@ impl App {
             current_screen: CurrentScreenMode::Main,
             current_editing: CurrentEditing::Selecting,
             yanke: String::new(),
+            command_line: CommandLine::new(),
+            last_search: String::new(),
+            options: Options::new(),
+            message: None,
+            messages: vec![],
+            show_messages: false,
+            user_commands: vec![],
+            aliases: HashMap::new(),
+            recover: false,
+            windows: vec![],
+            window_tree: WindowTree::Leaf(0),
+            current_window: 0,
+            colors: Colorscheme::new(ColorDepth::detect()),
+        }
+    }
+
+    pub fn open_file(&mut self, handle: std::fs::File, name: String, encoding: Option<&str>) {
+        let threshold = self.options.number(None, "largefile") as u64 * 1024 * 1024;
+        let size = handle.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        if encoding.is_none() && threshold > 0 && size >= threshold {
+            match LargeFile::open(handle) {
+                Ok(large) => {
+                    let mut file = File::new(name);
+                    file.set_saved(String::new());
+                    file.large = Some(large);
+                    self.files.push(file);
+                    self.current_screen = CurrentScreenMode::File(self.files.len() - 1);
+                    self.check_swap_files();
+                },
+                Err(error) => self.error(format!("\"{}\" {}", name, error)),
+            }
+            return;
+        }
+        let read = fileio::read_bytes(handle).and_then(|bytes| {
+            if encoding.is_none() && fileio::is_binary(&bytes) {
+                Ok(Err(bytes))
+            } else {
+                fileio::decode_bytes(&bytes, encoding).map(Ok)
+            }
+        });
+        let mut file = File::new(name);
+        if !fileio::is_writable(Path::new(&file.name)) {
+            file.local_options.insert("readonly", OptionValue::Bool(true));
+        }
+        match read {
+            Ok(Ok((text, format))) => {
+                file.set_format(format);
+                file.set_saved(text.clone());
+                file.undo_tree.add_node(text.clone(), Cursor::new(0, text.len()));
+            },
+            Ok(Err(bytes)) => {
+                file.set_saved(String::new());
+                file.hex = Some(HexView::new(bytes, false));
+                self.info(format!("\"{}\" is a binary file, opened in hex mode (:hex to edit it as text)", file.name));
+            },
+            Err(error) => {
+                self.error(format!("\"{}\" {}", file.name, error));
+                return;
+            },
+        }
+        self.files.push(file);
+        self.current_screen = CurrentScreenMode::File(self.files.len() - 1);
+        self.check_swap_files();
+    }
+
+    fn notify(&mut self, text: String, error: bool) {
+        self.messages.push(Message { text: text.clone(), error });
+        if self.messages.len() > 200 {
+            self.messages.remove(0);
+        }
+        self.message = Some(Message { text, error });
+    }
+
+    pub fn info(&mut self, text: String) {
+        self.notify(text, false);
+    }
+
+    pub fn error(&mut self, text: String) {
+        self.notify(text, true);
+    }
+
+    pub fn open_path(&mut self, name: String, encoding: Option<&str>) {
+        if name.is_empty() {
+            self.error("E32: No file name".to_owned());
+            return;
+        }
+        if Path::new(&name).is_dir() {
+            self.open_explorer(&name);
+            return;
+        }
+        match std::fs::File::open(&name) {
+            Ok(handle) => self.open_file(handle, name, encoding),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => self.open_new(name),
+            Err(error) => self.error(format!("\"{}\" {}", name, error)),
         }
     }
 
-    pub fn open_file(&mut self, handle: std::fs::File, name: String) {
-        let file = File::new(handle, name);
+    pub fn open_new(&mut self, name: String) {
+        let mut file = File::new(name);
+        file.new_file = true;
+        file.undo_tree.add_node(String::new(), Cursor::new(0, 0));
+        self.info(format!("\"{}\" [New]", file.name));
         self.files.push(file);
-        let index = self.files.len() - 1;
-        self.current_screen = CurrentScreenMode::File(index);
-        let mut text = String::new();
-        let _ = self.files[index].handle.read_to_string(&mut text);
-        self.files[index].saved_state = text.clone();
-        self.files[index].undo_tree.add_node(text.clone(), Cursor::new(0, text.len()));
+        self.current_screen = CurrentScreenMode::File(self.files.len() - 1);
+        self.check_swap_files();
     }
 
-    pub fn save_file(&mut self) {
-        if let CurrentScreenMode::File(index) = &self.current_screen {
-            if let Some((text, _)) = &self.files[*index].undo_tree.show_current_node() {
-                self.files[*index].saved_state = text.clone();
-           }
+    pub fn switch_to(&mut self, name: &str) -> bool {
+        if let Some(index) = self.files.iter().position(|file| file.name == name) {
+            self.current_screen = CurrentScreenMode::File(index);
+            true
+        } else {
+            false
         }
     }
 
-    pub fn quit_file(&mut self) -> std::io::Result<()> {
-        let i: i32 = if let CurrentScreenMode::File(index) = &self.current_screen {
-            *index as i32
-        } else {-1};
-        if i == -1 {
-            return Ok(());
+    pub fn line_key(&mut self, key: KeyEvent) {
+        let mut editing = std::mem::replace(&mut self.current_editing, CurrentEditing::Selecting);
+        let is_command = matches!(editing, CurrentEditing::Command(_));
+        let line = match &mut editing {
+            CurrentEditing::Command(line) | CurrentEditing::Search(line) => line,
+            _ => return,
+        };
+        if line.pending_register {
+            line.pending_register = false;
+            match key.code {
+                KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    if let Some(word) = self.word_under_cursor() {
+                        line.insert_str(&word);
+                    }
+                },
+                KeyCode::Char(c) => if let Some(register) = self.register(c) {
+                    line.insert_str(&register);
+                },
+                _ => (),
+            }
+            self.current_editing = editing;
+            return;
+        }
+        match key.code {
+            KeyCode::Esc => {
+                self.command_line.reset();
+                return;
+            },
+            KeyCode::Enter => {
+                let text = line.text.clone();
+                if is_command {
+                    self.command_line.push(&text, self.options.number(None, "history"));
+                    self.execute_command(text);
+                } else {
+                    self.search(text, true);
+                }
+                return;
+            },
+            KeyCode::Up if is_command => if let Some(text) = self.command_line.previous(&line.text) {
+                line.set(text);
+            },
+            KeyCode::Down if is_command => if let Some(text) = self.command_line.next() {
+                line.set(text);
+            },
+            KeyCode::Tab if is_command => {
+                let text = line.text.clone();
+                line.set(self.complete_command(&text, true));
+            },
+            KeyCode::BackTab if is_command => {
+                let text = line.text.clone();
+                line.set(self.complete_command(&text, false));
+            },
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => line.pending_register = true,
+            _ => if line.edit(key) {
+                self.command_line.reset();
+            },
+        }
+        self.current_editing = editing;
+    }
+
+    pub fn register(&self, name: char) -> Option<String> {
+        let register = match name {
+            '"' | '0' => self.yanke.clone(),
+            ':' => self.command_line.last()?.clone(),
+            '/' => self.last_search.clone(),
+            '%' => match self.current_screen {
+                CurrentScreenMode::File(index) => self.files[index].name.clone(),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        if register.is_empty() { None } else { Some(register) }
+    }
+
+    pub fn word_under_cursor(&self) -> Option<String> {
+        let index = match self.current_screen {
+            CurrentScreenMode::File(index) => index,
+            _ => return None,
+        };
+        let (text, cursor) = self.files[index].undo_tree.show_current_node()?;
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let cursor = cursor.min(text.len());
+        let start = text[..cursor].rfind(|c| !is_word(c)).map(|i| i + text[i..].chars().next().unwrap().len_utf8()).unwrap_or(0);
+        let end = text[cursor..].find(|c| !is_word(c)).map(|i| cursor + i).unwrap_or(text.len());
+        if start < end { Some(text[start..end].to_owned()) } else { None }
+    }
+
+    pub fn search(&mut self, pattern: String, forward: bool) {
+        if !pattern.is_empty() {
+            self.last_search = pattern;
+        }
+        self.current_editing = CurrentEditing::Selecting;
+        let index = match self.current_screen {
+            CurrentScreenMode::File(index) => index,
+            _ => return,
+        };
+        let pattern = &self.last_search;
+        if pattern.is_empty() {
+            self.error("E35: No previous regular expression".to_owned());
+            return;
+        }
+        if let Some(large) = self.files[index].large.as_mut() {
+            large.search(pattern, forward);
+            return;
+        }
+        if self.files[index].hex.is_some() {
+            return;
+        }
+        if self.files[index].explorer.is_some() {
+            let pattern = pattern.clone();
+            self.explorer_search(index, &pattern, forward);
+            return;
+        }
+        let local = Some(&self.files[index].local_options);
+        let ignore_case = self.options.bool(local, "ignorecase")
+            && !(self.options.bool(local, "smartcase") && pattern.chars().any(|c| c.is_uppercase()));
+        if let Some((text, cursor)) = self.files[index].undo_tree.show_current_node() {
+            let (text, pattern) = if ignore_case {
+                (text.to_ascii_lowercase(), pattern.to_ascii_lowercase())
+            } else {
+                (text, pattern.clone())
+            };
+            let found = if forward {
+                let start = text[cursor..].chars().next().map(|c| cursor + c.len_utf8()).unwrap_or(cursor);
+                text[start..].find(pattern.as_str()).map(|i| start + i)
+                    .or_else(|| text.find(pattern.as_str()))
+            } else {
+                text[..cursor].rfind(pattern.as_str())
+                    .or_else(|| text.rfind(pattern.as_str()))
+            };
+            if let Some(found) = found {
+                self.files[index].undo_tree.set_cursor(found);
+            }
+        }
+    }
+
+    pub fn complete_command(&mut self, line: &str, forward: bool) -> String {
+        let buffers: Vec<&str> = self.files.iter().map(|file| file.name.as_str()).collect();
+        let options: Vec<&str> = self.options.names().collect();
+        let commands: Vec<&str> = self.user_commands
+            .iter()
+            .map(|command| command.name.as_str())
+            .chain(self.aliases.keys().map(|alias| alias.as_str()))
+            .collect();
+        self.command_line.complete(line, &CompletionSources { buffers, options, commands }, forward)
+    }
+
+    pub fn local_options(&self) -> Option<&LocalOptions> {
+        match self.current_screen {
+            CurrentScreenMode::File(index) => Some(&self.files[index].local_options),
+            _ => None,
         }
-        let i : usize = i.try_into().unwrap();
+    }
 
-        let mut handle = self.files[i].handle.try_clone().unwrap();
-        let data = self.files[i].saved_state.to_owned();
+    pub fn insert_tab(&mut self, index: usize) {
+        let local = Some(&self.files[index].local_options);
+        let tab = if self.options.bool(local, "expandtab") {
+            let width = match self.options.number(local, "shiftwidth") {
+                0 => self.options.number(local, "tabstop"),
+                width => width,
+            };
+            " ".repeat(width)
+        } else {
+            "\t".to_owned()
+        };
+        match self.files[index].large.as_mut() {
+            Some(large) => if let Err(error) = large.insert(&tab) {
+                self.error(error);
+            },
+            None => for c in tab.chars() {
+                self.files[index].undo_tree.add_char(c);
+            },
+        }
+    }
 
-        handle.rewind()?;
-        handle.write_all(data.as_bytes())?;
-        handle.set_len(data.len() as u64)?;
-        
+    pub fn save_file(&mut self, index: usize, force: bool) -> bool {
+        if !force && self.files[index].changed_on_disk() && fileio::disk_state(Path::new(&self.files[index].name)).is_some() {
+            self.error("WARNING: The file has been changed since reading it (add ! to override)".to_owned());
+            return false;
+        }
+        if self.files[index].explorer.is_some() {
+            self.error(format!("E502: \"{}\" is a directory", self.files[index].name));
+            return false;
+        }
+        if self.files[index].scratch {
+            self.error(format!("E382: Cannot write, \"{}\" is a scratch buffer (use w FILE)", self.files[index].name));
+            return false;
+        }
+        if !force && self.options.bool(Some(&self.files[index].local_options), "readonly") {
+            self.error("E45: 'readonly' option is set (add ! to override)".to_owned());
+            return false;
+        }
+        match self.files[index].write(&self.options) {
+            Ok(written) => {
+                self.info(written);
+                true
+            },
+            Err(error) => {
+                self.error(write_error(&self.files[index].name, error));
+                false
+            },
+        }
+    }
+
+    pub fn write_to(&mut self, index: usize, name: &str) -> bool {
+        if let Some(hex) = &self.files[index].hex {
+            return match fileio::write_atomic(Path::new(name), &hex.bytes) {
+                Ok(_) => {
+                    self.info(format!("\"{}\" {}B written", name, hex.bytes.len()));
+                    true
+                },
+                Err(error) => {
+                    self.error(write_error(name, error));
+                    false
+                },
+            };
+        }
+        if let Some(large) = self.files[index].large.as_mut() {
+            return match large.save(Path::new(name)) {
+                Ok((lines, bytes)) => {
+                    self.info(format!("\"{}\" {}L, {}B written", name, lines, bytes));
+                    true
+                },
+                Err(error) => {
+                    self.error(write_error(name, error));
+                    false
+                },
+            };
+        }
+        let text = self.files[index].text();
+        let format = self.files[index].format(&self.options);
+        match fileio::encode_text(&text, &format).and_then(|data| fileio::write_atomic(Path::new(name), &data).map(|_| data)) {
+            Ok(data) => {
+                self.info(written_message(name, &text, &data, &format));
+                true
+            },
+            Err(error) => {
+                self.error(write_error(name, error));
+                false
+            },
+        }
+    }
+
+    pub fn quit_file(&mut self, force: bool) -> Result<(), String> {
+        let i = match self.current_screen {
+            CurrentScreenMode::File(index) => index,
+            _ => return Ok(()),
+        };
+        if !force && self.files[i].is_modified() {
+            return Err("E37: No write since last change (add ! to override)".to_owned());
+        }
+
+        self.remove_swap(i);
         self.files.remove(i);
+        if self.remove_file_windows(i) {
+            return Ok(());
+        }
         self.current_screen = if self.files.len() != 0 {
             if i as i32 -1 > -1 {
                 CurrentScreenMode::File(i - 1)
@@ -99,48 +593,201 @@ impl App {
         Ok(())
     }
 
+    fn write_quit(&mut self, chars: &str, argument: Option<&str>) {
+        let (chars, force) = match chars.strip_suffix('!') {
+            Some(chars) => (chars, true),
+            None => (chars, false),
+        };
+        let all = chars.contains('a');
+        if chars.contains('w') {
+            if all {
+                for index in 0..self.files.len() {
+                    if self.files[index].is_modified() && !self.save_file(index, force) {
+                        return;
+                    }
+                }
+            } else if let CurrentScreenMode::File(index) = self.current_screen {
+                let written = match argument {
+                    Some(name) => self.write_to(index, name),
+                    None => self.save_file(index, force),
+                };
+                if !written {
+                    return;
+                }
+            } else {
+                self.error("E32: No file name".to_owned());
+                return;
+            }
+        }
+        if chars.contains('q') {
+            if all {
+                if !force && let Some(file) = self.files.iter().find(|file| file.is_modified()) {
+                    let error = format!("E162: No write since last change for buffer \"{}\"", file.name);
+                    self.error(error);
+                    return;
+                }
+                self.remove_swap_files();
+                self.files.clear();
+                self.current_screen = CurrentScreenMode::Main;
+            } else if self.windows.len() > 1 {
+                if let Err(error) = self.close_window(self.current_window) {
+                    self.error(error);
+                }
+            } else if let Err(error) = self.quit_file(force) {
+                self.error(error);
+            }
+        }
+    }
+
     pub fn execute_command(&mut self, command: String) {
+        self.message = None;
+        self.current_editing = CurrentEditing::Selecting;
+        let command = match self.expand_user_command(&command) {
+            Ok(command) => command,
+            Err(error) => {
+                self.error(error);
+                return;
+            },
+        };
+        if let CurrentScreenMode::File(index) = self.current_screen
+            && let Some(result) = self.execute_line_command(index, &command) {
+            if let Err(error) = result {
+                self.error(error);
+            }
+            return;
+        }
         let mut command = command.split_ascii_whitespace();
         match command.next() {
+            Some(name @ ("se" | "set" | "setl" | "setlocal")) => {
+                let local = match self.current_screen {
+                    CurrentScreenMode::File(index) => Some(&mut self.files[index].local_options),
+                    _ => None,
+                };
+                match self.options.set(local, command.remainder().unwrap_or(""), name.starts_with("setl")) {
+                    Ok(shown) => if !shown.is_empty() {
+                        self.info(shown.join("  "));
+                    },
+                    Err(error) => self.error(error),
+                }
+            },
+            Some("mes" | "messages") => match command.next() {
+                Some("clear") => {
+                    self.messages.clear();
+                    self.message = None;
+                },
+                _ => self.show_messages = true,
+            },
+            Some(name @ ("com" | "command" | "com!" | "command!")) => {
+                let bang = if name.ends_with('!') { "!" } else { "" };
+                self.define_command(&format!("{}{}", bang, command.remainder().unwrap_or("")));
+            },
+            Some("delc" | "delcommand") => {
+                let name = command.next().unwrap_or("");
+                self.delete_command(name);
+            },
+            Some("comc" | "comclear") => self.user_commands.clear(),
+            Some("alias") => self.define_alias(command.remainder().unwrap_or("")),
+            Some("unalias") => {
+                let name = command.next().unwrap_or("");
+                if self.aliases.remove(name).is_none() {
+                    self.error(format!("E184: No such alias: {}", name));
+                }
+            },
             Some("tabnew") => {
-                let name : String = command.next().unwrap_or("").to_string();
-                let handle = std::fs::File::options()
-                        .read(true)
-                        .write(true)
-                        .create(true)
-                        .open(&name).unwrap();
-                self.open_file(handle, name);
-            },
-            Some(chars) => {
-                if let Some(_) = chars.find('a') {
-                    let files_len = self.files.len();
-                    for _file in 0..files_len {
-                        for c in chars.chars() {
-                            match c {
-                                'w' => self.save_file(),
-                                'q' => if let Ok(_) = self.quit_file() {
-                                    self.current_editing = CurrentEditing::Selecting;
-                                    break;
-                                },
-                                _ => (),
-                            }
-                        }
-                    }
-                } else  {
-                    for c in chars.chars() {
-                        match c {
-                            'w' => self.save_file(),
-                            'q' => if let Ok(_) = self.quit_file() {
-                                self.current_editing = CurrentEditing::Selecting;
+                let name = split_args(command.remainder().unwrap_or("")).into_iter().next().unwrap_or_default();
+                self.open_path(name, None);
+            },
+            Some(edit @ ("e" | "edit" | "e!" | "edit!")) => {
+                let mut encoding = None;
+                let mut name = String::new();
+                for arg in split_args(command.remainder().unwrap_or("")) {
+                    match arg.strip_prefix("++enc=").or_else(|| arg.strip_prefix("++encoding=")) {
+                        Some(value) => match fileio::encoding_name(value) {
+                            Some(value) => encoding = Some(value),
+                            None => {
+                                self.error(format!("E474: Invalid argument: {}", arg));
                                 return;
                             },
-                            _ => (),
+                        },
+                        None => name = arg,
+                    }
+                }
+                let current = match self.current_screen {
+                    CurrentScreenMode::File(index) if name.is_empty() || self.files[index].name == name => Some(index),
+                    _ => None,
+                };
+                match current {
+                    Some(index) if edit.ends_with('!') || encoding.is_some() => {
+                        if !edit.ends_with('!') && self.files[index].is_modified() {
+                            self.error("E37: No write since last change (add ! to override)".to_owned());
+                        } else {
+                            self.reload_file(index, encoding);
                         }
+                    },
+                    _ => if !self.switch_to(&name) {
+                        self.open_path(name, encoding);
+                    },
+                }
+            },
+            Some("checkt" | "checktime") => self.check_time(),
+            Some("mkdir") => match split_args(command.remainder().unwrap_or("")).as_slice() {
+                [] => self.make_directory(""),
+                [name] => self.make_directory(name),
+                _ => self.error("E488: Trailing characters".to_owned()),
+            },
+            Some("rename") => match split_args(command.remainder().unwrap_or("")).as_slice() {
+                [from, to] => self.rename_path(from, to),
+                [_, _, _, ..] => self.error("E488: Trailing characters".to_owned()),
+                _ => self.rename_path("", ""),
+            },
+            Some("hex") => if let CurrentScreenMode::File(index) = self.current_screen {
+                if self.files[index].explorer.is_some() {
+                    self.error(format!("E502: \"{}\" is a directory", self.files[index].name));
+                } else if self.files[index].large.is_some() {
+                    self.error(format!("\"{}\": no hex mode for large files", self.files[index].name));
+                } else {
+                    self.toggle_hex(index);
+                }
+            },
+            Some("sp" | "split") => self.split_window(Direction::Vertical, split_args(command.remainder().unwrap_or("")).first().map(String::as_str)),
+            Some("vs" | "vsplit") => self.split_window(Direction::Horizontal, split_args(command.remainder().unwrap_or("")).first().map(String::as_str)),
+            Some("clo" | "close") => if let Err(error) = self.close_window(self.current_window) {
+                self.error(error);
+            },
+            Some("on" | "only") => self.only_window(),
+            Some("sig" | "sign") => self.sign_command(command.remainder().unwrap_or("")),
+            Some("colo" | "colorscheme") => self.set_colorscheme(command.next().unwrap_or("")),
+            Some(name @ ("res" | "resize" | "vert" | "vertical")) => {
+                let direction = if name.starts_with('v') {
+                    if !matches!(command.next(), Some("res" | "resize")) {
+                        self.error("E471: Argument required".to_owned());
+                        return;
                     }
+                    Direction::Horizontal
+                } else {
+                    Direction::Vertical
+                };
+                if let Err(error) = self.resize_command(direction, command.next().unwrap_or("")) {
+                    self.error(error);
                 }
-            }
+            },
+            Some("b" | "buffer") => {
+                let args = split_args(command.remainder().unwrap_or(""));
+                let name = args.first().map_or("", String::as_str);
+                if !self.switch_to(name) {
+                    if let Some(file) = self.files.iter().find(|file| file.name.contains(name)) {
+                        let name = file.name.clone();
+                        self.switch_to(&name);
+                    } else {
+                        self.error(format!("E94: No matching buffer for {}", name));
+                    }
+                }
+            },
+            Some(chars) if chars.trim_end_matches('!').is_empty() || !chars.trim_end_matches('!').chars().all(|c| matches!(c, 'w' | 'q' | 'a')) => {
+                self.error(format!("E492: Not an editor command: {}", chars));
+            },
+            Some(chars) => self.write_quit(chars, split_args(command.remainder().unwrap_or("")).first().map(String::as_str)),
             _ => (),
         }
-        self.current_editing = CurrentEditing::Selecting;
     }
 }
@@ -0,0 +1,190 @@
+use std::path::Path;
+
+use crate::app::{
+    fileio,
+    hex::HexView,
+    largefile::LargeFile,
+    prompt::{Prompt, PromptAction},
+    App, CurrentEditing, CurrentScreenMode, File,
+};
+
+impl App {
+    pub fn check_time(&mut self) {
+        if let CurrentEditing::Prompt(_) = self.current_editing {
+            return;
+        }
+        self.refresh_explorers();
+        for index in 0..self.files.len() {
+            if !self.files[index].changed_on_disk() {
+                continue;
+            }
+            let name = self.files[index].name.clone();
+            if fileio::disk_state(Path::new(&name)).is_none() {
+                self.files[index].disk_state = None;
+                self.error(format!("E211: File \"{}\" no longer available", name));
+                continue;
+            }
+            if !self.files[index].is_modified() && self.options.bool(Some(&self.files[index].local_options), "autoread") {
+                self.reload_file(index, None);
+                continue;
+            }
+            let message = if self.files[index].is_modified() {
+                format!("W12: Warning: File \"{}\" has changed and the buffer was changed in Rime as well", name)
+            } else {
+                format!("W11: Warning: File \"{}\" has changed since editing started", name)
+            };
+            self.current_editing = CurrentEditing::Prompt(Prompt {
+                message,
+                choices: vec![
+                    ('r', "Reload", PromptAction::Reload(index)),
+                    ('k', "Keep", PromptAction::Keep(index)),
+                    ('d', "Diff", PromptAction::Diff(index)),
+                ],
+                default: 1,
+            });
+            return;
+        }
+    }
+
+    pub fn reload_file(&mut self, index: usize, encoding: Option<&str>) {
+        let name = self.files[index].name.clone();
+        if self.files[index].large.is_some() {
+            match std::fs::File::open(&name).and_then(LargeFile::open) {
+                Ok(large) => {
+                    self.files[index].large = Some(large);
+                    self.files[index].set_saved(String::new());
+                    self.info(format!("\"{}\" reloaded", name));
+                },
+                Err(error) => self.error(format!("E211: File \"{}\" no longer available: {}", name, error)),
+            }
+            return;
+        }
+        if self.files[index].hex.is_some() {
+            if encoding.is_none() {
+                match std::fs::File::open(&name).and_then(fileio::read_bytes) {
+                    Ok(bytes) => {
+                        self.files[index].hex = Some(HexView::new(bytes, false));
+                        self.files[index].set_saved(String::new());
+                        self.info(format!("\"{}\" reloaded", name));
+                    },
+                    Err(error) => self.error(format!("E211: File \"{}\" no longer available: {}", name, error)),
+                }
+                return;
+            }
+            self.files[index].hex = None;
+        }
+        match std::fs::File::open(&name).and_then(|handle| fileio::read_text(handle, encoding)) {
+            Ok((text, format)) => {
+                let cursor = self.files[index].undo_tree.show_current_node().map(|(_, cursor)| cursor).unwrap_or(0);
+                self.files[index].follow_signs(|undo_tree| undo_tree.replace_text(text.clone(), cursor));
+                self.files[index].set_format(format);
+                self.files[index].set_saved(text);
+                self.info(format!("\"{}\" reloaded", name));
+            },
+            Err(error) => self.error(format!("E211: File \"{}\" no longer available: {}", name, error)),
+        }
+    }
+
+    pub fn keep_file(&mut self, index: usize) {
+        self.files[index].disk_state = fileio::disk_state(Path::new(&self.files[index].name));
+    }
+
+    pub fn diff_file(&mut self, index: usize) {
+        let name = self.files[index].name.clone();
+        if self.files[index].large.is_some() || self.files[index].hex.is_some() {
+            self.keep_file(index);
+            self.error(format!("\"{}\": no diff available for large files or in hex mode", name));
+            return;
+        }
+        let disk = match std::fs::File::open(&name).and_then(|handle| fileio::read_text(handle, None)) {
+            Ok((text, _)) => text,
+            Err(error) => {
+                self.error(format!("E211: File \"{}\" no longer available: {}", name, error));
+                return;
+            },
+        };
+        let diff = format!("--- {} (buffer)\n+++ {} (disk)\n{}", name, name, line_diff(&self.files[index].text(), &disk));
+        self.keep_file(index);
+        let mut file = File::new(format!("[diff] {}", name));
+        file.scratch = true;
+        file.undo_tree.replace_text(diff, 0);
+        self.files.push(file);
+        self.current_screen = CurrentScreenMode::File(self.files.len() - 1);
+        self.info(format!("Use :e! in \"{}\" to load the version on disk", name));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+pub fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Change> {
+    let prefix = old.iter().zip(new.iter()).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    let mut changes = vec![Change::Same; prefix];
+    let (n, m) = (old_middle.len(), new_middle.len());
+    if n * m > 4_000_000 {
+        changes.extend(std::iter::repeat_n(Change::Removed, n));
+        changes.extend(std::iter::repeat_n(Change::Added, m));
+    } else {
+        let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i * (m + 1) + j] = if old_middle[i] == new_middle[j] {
+                    lengths[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_middle[i] == new_middle[j] {
+                changes.push(Change::Same);
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1]) {
+                changes.push(Change::Removed);
+                i += 1;
+            } else {
+                changes.push(Change::Added);
+                j += 1;
+            }
+        }
+    }
+    changes.extend(std::iter::repeat_n(Change::Same, suffix));
+    changes
+}
+
+fn line_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let changes = diff_lines(&old, &new);
+    let prefix = changes.iter().take_while(|change| **change == Change::Same).count();
+    let suffix = changes[prefix..].iter().rev().take_while(|change| **change == Change::Same).count();
+    let mut diff = format!("@@ line {} @@\n", prefix + 1);
+    let (mut i, mut j) = (prefix, prefix);
+    for change in &changes[prefix..changes.len() - suffix] {
+        match change {
+            Change::Same => {
+                diff += &format!("  {}\n", old[i]);
+                i += 1;
+                j += 1;
+            },
+            Change::Removed => {
+                diff += &format!("- {}\n", old[i]);
+                i += 1;
+            },
+            Change::Added => {
+                diff += &format!("+ {}\n", new[j]);
+                j += 1;
+            },
+        }
+    }
+    diff
+}
@@ -0,0 +1,298 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::app::App;
+
+const BUILTIN: [(&str, &str); 2] = [
+    ("rime", include_str!("../../colors/rime.theme")),
+    ("light", include_str!("../../colors/light.theme")),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Group {
+    Normal,
+    Keyword,
+    Constant,
+    String,
+    Macro,
+    Delimiter,
+    Operator,
+    Comment,
+    CursorLine,
+    LineNr,
+    SignColumn,
+    FoldColumn,
+    NonText,
+    SpecialKey,
+    CursorLineNr,
+    StatusLine,
+    TabLine,
+    TabLineSel,
+    Border,
+    BorderSel,
+    Pmenu,
+    PmenuSel,
+    Directory,
+    Message,
+    ErrorMsg,
+    Question,
+}
+
+const GROUPS: [(&str, Group); 26] = [
+    ("Normal", Group::Normal),
+    ("Keyword", Group::Keyword),
+    ("Constant", Group::Constant),
+    ("String", Group::String),
+    ("Macro", Group::Macro),
+    ("Delimiter", Group::Delimiter),
+    ("Operator", Group::Operator),
+    ("Comment", Group::Comment),
+    ("CursorLine", Group::CursorLine),
+    ("LineNr", Group::LineNr),
+    ("SignColumn", Group::SignColumn),
+    ("FoldColumn", Group::FoldColumn),
+    ("NonText", Group::NonText),
+    ("SpecialKey", Group::SpecialKey),
+    ("CursorLineNr", Group::CursorLineNr),
+    ("StatusLine", Group::StatusLine),
+    ("TabLine", Group::TabLine),
+    ("TabLineSel", Group::TabLineSel),
+    ("Border", Group::Border),
+    ("BorderSel", Group::BorderSel),
+    ("Pmenu", Group::Pmenu),
+    ("PmenuSel", Group::PmenuSel),
+    ("Directory", Group::Directory),
+    ("Message", Group::Message),
+    ("ErrorMsg", Group::ErrorMsg),
+    ("Question", Group::Question),
+];
+
+const ANSI: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorDepth {
+    pub fn detect() -> ColorDepth {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn palette_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI[index as usize].1,
+        16..=231 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let n = index - 16;
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        },
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        },
+    }
+}
+
+fn to_256(rgb: (u8, u8, u8)) -> u8 {
+    let cube = |v: u8| if v < 48 { 0 } else if v < 115 { 1 } else { (v - 35) / 40 };
+    let cube_index = 16 + 36 * cube(rgb.0) + 6 * cube(rgb.1) + cube(rgb.2);
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray_index = if average > 238 { 255 } else { 232 + (average.saturating_sub(3) / 10) as u8 };
+    if distance(rgb, palette_rgb(gray_index)) < distance(rgb, palette_rgb(cube_index)) {
+        gray_index
+    } else {
+        cube_index
+    }
+}
+
+fn to_16(rgb: (u8, u8, u8)) -> Color {
+    ANSI.iter().min_by_key(|(_, ansi)| distance(rgb, *ansi)).map(|(color, _)| *color).unwrap_or(Color::Reset)
+}
+
+fn downgrade(color: Color, depth: ColorDepth) -> Color {
+    match (color, depth) {
+        (Color::Rgb(r, g, b), ColorDepth::Ansi256) => Color::Indexed(to_256((r, g, b))),
+        (Color::Rgb(r, g, b), ColorDepth::Ansi16) => to_16((r, g, b)),
+        (Color::Indexed(index), ColorDepth::Ansi16) => to_16(palette_rgb(index)),
+        (color, _) => color,
+    }
+}
+
+fn parse_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+        return match hex.len() {
+            6 => Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?)),
+            _ => None,
+        };
+    }
+    if let Ok(index) = value.parse::<u8>() {
+        return Some(Color::Indexed(index));
+    }
+    match value.to_ascii_lowercase().as_str() {
+        "reset" | "none" => Some(Color::Reset),
+        "black" => Some(Color::Black),
+        "red" => Some(Color::Red),
+        "green" => Some(Color::Green),
+        "yellow" => Some(Color::Yellow),
+        "blue" => Some(Color::Blue),
+        "magenta" => Some(Color::Magenta),
+        "cyan" => Some(Color::Cyan),
+        "gray" | "grey" => Some(Color::Gray),
+        "darkgray" | "darkgrey" => Some(Color::DarkGray),
+        "lightred" => Some(Color::LightRed),
+        "lightgreen" => Some(Color::LightGreen),
+        "lightyellow" => Some(Color::LightYellow),
+        "lightblue" => Some(Color::LightBlue),
+        "lightmagenta" => Some(Color::LightMagenta),
+        "lightcyan" => Some(Color::LightCyan),
+        "white" => Some(Color::White),
+        _ => None,
+    }
+}
+
+pub fn group(name: &str) -> Option<Group> {
+    GROUPS.iter().find(|(group, _)| *group == name).map(|(_, group)| *group)
+}
+
+fn parse_theme(source: &str, groups: &mut HashMap<Group, Style>) -> Result<(), String> {
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let Some(group) = group(name) else {
+            return Err(format!("E411: Highlight group not found: {} (line {})", name, number + 1));
+        };
+        let mut style = Style::default();
+        for word in words {
+            let invalid = || format!("E475: Invalid argument: {} (line {})", word, number + 1);
+            style = match word.split_once('=') {
+                Some(("fg", value)) => style.fg(parse_color(value).ok_or_else(invalid)?),
+                Some(("bg", value)) => style.bg(parse_color(value).ok_or_else(invalid)?),
+                None if word == "bold" => style.add_modifier(Modifier::BOLD),
+                None if word == "italic" => style.add_modifier(Modifier::ITALIC),
+                None if word == "underline" => style.add_modifier(Modifier::UNDERLINED),
+                None if word == "reversed" => style.add_modifier(Modifier::REVERSED),
+                _ => return Err(invalid()),
+            };
+        }
+        groups.insert(group, style);
+    }
+    Ok(())
+}
+
+fn theme_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|config| config.join("rime").join("colors"))
+}
+
+pub fn colorscheme_names() -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN.iter().map(|(name, _)| name.to_string()).collect();
+    if let Some(dir) = theme_dir() {
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "theme") && let Some(name) = path.file_stem() {
+                names.push(name.to_string_lossy().into_owned());
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+pub struct Colorscheme {
+    pub name: String,
+    groups: HashMap<Group, Style>,
+    depth: ColorDepth,
+}
+
+impl Colorscheme {
+    pub fn new(depth: ColorDepth) -> Colorscheme {
+        let mut groups = HashMap::new();
+        let _ = parse_theme(BUILTIN[0].1, &mut groups);
+        Colorscheme { name: BUILTIN[0].0.to_owned(), groups, depth }
+    }
+
+    pub fn load(name: &str, depth: ColorDepth) -> Result<Colorscheme, String> {
+        let path = theme_dir().map(|dir| dir.join(format!("{}.theme", name)));
+        let source = match path.as_ref().and_then(|path| fs::read_to_string(path).ok()) {
+            Some(source) => source,
+            None => match BUILTIN.iter().find(|(builtin, _)| *builtin == name) {
+                Some((_, source)) => source.to_string(),
+                None => return Err(format!("E185: Cannot find color scheme '{}'", name)),
+            },
+        };
+        let mut colorscheme = Colorscheme::new(depth);
+        parse_theme(&source, &mut colorscheme.groups)?;
+        colorscheme.name = name.to_owned();
+        Ok(colorscheme)
+    }
+
+    pub fn get(&self, group: Group) -> Style {
+        let style = self.groups.get(&group).copied().unwrap_or_default();
+        Style {
+            fg: style.fg.map(|color| downgrade(color, self.depth)),
+            bg: style.bg.map(|color| downgrade(color, self.depth)),
+            ..style
+        }
+    }
+}
+
+impl App {
+    pub fn set_colorscheme(&mut self, name: &str) {
+        if name.is_empty() {
+            self.info(self.colors.name.clone());
+            return;
+        }
+        match Colorscheme::load(name, ColorDepth::detect()) {
+            Ok(colors) => {
+                self.colors = colors;
+                for window in &self.windows {
+                    window.lines.borrow_mut().clear();
+                }
+            },
+            Err(error) => self.error(error),
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use std::{fs, io::Write, path::PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::{colorscheme::colorscheme_names, options::{escape_arg, split_args}};
+
+pub const COMMANDS: &[&str] = &[
+    "a", "alias", "b", "buffer", "checktime", "close", "colorscheme", "comclear", "command", "delcommand", "delete", "e", "edit",
+    "global", "hex", "messages", "mkdir", "move", "normal", "only", "q", "qa", "quit", "rename", "resize", "set",
+    "setlocal", "sign", "split", "substitute", "tabnew", "unalias", "vertical", "vglobal", "vsplit", "w", "wa", "wq", "wqa",
+];
+pub struct LineEditor {
+    pub text: String,
+    pub cursor: usize,
+    pub pending_register: bool,
+}
+
+impl LineEditor {
+    pub fn new() -> LineEditor {
+        LineEditor {
+            text: String::new(),
+            cursor: 0,
+            pending_register: false,
+        }
+    }
+
+    pub fn set(&mut self, text: String) {
+        self.cursor = text.len();
+        self.text = text;
+    }
+
+    pub fn insert_str(&mut self, s: &str) {
+        self.text.insert_str(self.cursor, s);
+        self.cursor += s.len();
+    }
+
+    fn previous_boundary(&self) -> usize {
+        self.text[..self.cursor].char_indices().last().map(|(i, _)| i).unwrap_or(0)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.text[self.cursor..].chars().next().map(|c| self.cursor + c.len_utf8()).unwrap_or(self.cursor)
+    }
+
+    pub fn edit(&mut self, key: KeyEvent) -> bool {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Left => self.cursor = self.previous_boundary(),
+            KeyCode::Right => self.cursor = self.next_boundary(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            KeyCode::Char('b') if control => self.cursor = 0,
+            KeyCode::Char('e') if control => self.cursor = self.text.len(),
+            KeyCode::Char('w') if control => {
+                let before = &self.text[..self.cursor];
+                let trimmed = before.trim_end();
+                let start = match trimmed.chars().last() {
+                    Some(c) if c.is_alphanumeric() || c == '_' => trimmed
+                        .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .map(|i| i + trimmed[i..].chars().next().unwrap().len_utf8())
+                        .unwrap_or(0),
+                    Some(c) => trimmed.len() - c.len_utf8(),
+                    None => 0,
+                };
+                self.text.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            },
+            KeyCode::Char('u') if control => {
+                self.text.replace_range(..self.cursor, "");
+                self.cursor = 0;
+            },
+            KeyCode::Backspace => if self.cursor > 0 {
+                let start = self.previous_boundary();
+                self.text.replace_range(start..self.cursor, "");
+                self.cursor = start;
+            },
+            KeyCode::Delete => {
+                let end = self.next_boundary();
+                self.text.replace_range(self.cursor..end, "");
+            },
+            KeyCode::Char(c) if !control => {
+                self.text.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+pub struct CompletionSources<'a> {
+    pub buffers: Vec<&'a str>,
+    pub options: Vec<&'a str>,
+    pub commands: Vec<&'a str>,
+}
+
+pub struct CommandLine {
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+    history_index: Option<usize>,
+    history_prefix: String,
+    pub completions: Vec<String>,
+    pub completion_index: usize,
+    completion_base: String,
+}
+
+impl CommandLine {
+    pub fn new() -> CommandLine {
+        let history_path = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".rime_history"));
+        let history = match &history_path {
+            Some(path) => fs::read_to_string(path)
+                .unwrap_or_default()
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(|line| line.to_owned())
+                .collect(),
+            None => vec![],
+        };
+        CommandLine {
+            history,
+            history_path,
+            history_index: None,
+            history_prefix: String::new(),
+            completions: vec![],
+            completion_index: 0,
+            completion_base: String::new(),
+        }
+    }
+
+    pub fn push(&mut self, command: &str, max: usize) {
+        self.reset();
+        if command.trim().is_empty() {
+            return;
+        }
+        self.history.retain(|entry| entry != command);
+        self.history.push(command.to_owned());
+        if self.history.len() > max {
+            self.history.drain(..self.history.len() - max);
+        }
+        if let Some(path) = &self.history_path && let Ok(mut file) = fs::File::create(path) {
+            let _ = file.write_all((self.history.join("\n") + "\n").as_bytes());
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.history_index = None;
+        self.completions.clear();
+        self.completion_index = 0;
+    }
+
+    pub fn last(&self) -> Option<&String> {
+        self.history.last()
+    }
+
+    pub fn previous(&mut self, current: &str) -> Option<String> {
+        let end = match self.history_index {
+            Some(index) => index,
+            None => {
+                self.history_prefix = current.to_owned();
+                self.history.len()
+            }
+        };
+        let found = self.history[..end]
+            .iter()
+            .rposition(|entry| entry.starts_with(&self.history_prefix))?;
+        self.history_index = Some(found);
+        self.completions.clear();
+        Some(self.history[found].clone())
+    }
+
+    pub fn next(&mut self) -> Option<String> {
+        let start = self.history_index? + 1;
+        self.completions.clear();
+        match self.history[start..]
+            .iter()
+            .position(|entry| entry.starts_with(&self.history_prefix)) {
+            Some(offset) => {
+                self.history_index = Some(start + offset);
+                Some(self.history[start + offset].clone())
+            },
+            None => {
+                self.history_index = None;
+                Some(self.history_prefix.clone())
+            },
+        }
+    }
+
+    pub fn complete(&mut self, line: &str, sources: &CompletionSources, forward: bool) -> String {
+        if !self.completions.is_empty() && line == self.completion_base.clone() + &self.completions[self.completion_index] {
+            let len = self.completions.len();
+            self.completion_index = if forward {
+                (self.completion_index + 1) % len
+            } else {
+                (self.completion_index + len - 1) % len
+            };
+            return self.completion_base.clone() + &self.completions[self.completion_index];
+        }
+        let (start, candidates) = candidates(line, sources);
+        if candidates.is_empty() {
+            self.completions.clear();
+            return line.to_owned();
+        }
+        self.completion_base = line[..start].to_owned();
+        self.completion_index = if forward { 0 } else { candidates.len() - 1 };
+        let completed = self.completion_base.clone() + &candidates[self.completion_index];
+        if candidates.len() > 1 {
+            self.completions = candidates;
+        } else {
+            self.completions.clear();
+        }
+        completed
+    }
+}
+
+fn word_start(line: &str) -> usize {
+    let mut start = 0;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c.is_whitespace() {
+            start = i + c.len_utf8();
+        }
+    }
+    start
+}
+
+fn candidates(line: &str, sources: &CompletionSources) -> (usize, Vec<String>) {
+    let start = word_start(line);
+    let word = &line[start..];
+    let unescaped = split_args(word).pop().unwrap_or_default();
+    let mut words = line[..start].split_whitespace();
+    let candidates = match words.next() {
+        None => {
+            let mut commands: Vec<String> = COMMANDS
+                .iter()
+                .chain(sources.commands.iter())
+                .filter(|command| command.starts_with(word))
+                .map(|command| command.to_string())
+                .collect();
+            commands.sort();
+            commands.dedup();
+            commands
+        },
+        Some("e" | "edit" | "tabnew" | "w" | "write" | "mkdir" | "rename" | "sp" | "split" | "vs" | "vsplit") => complete_path(&unescaped).iter().map(|path| escape_arg(path)).collect(),
+        Some("b" | "buffer") => sources.buffers
+            .iter()
+            .filter(|name| name.starts_with(&unescaped))
+            .map(|name| escape_arg(name))
+            .collect(),
+        Some("se" | "set" | "setl" | "setlocal") => {
+            let (negation, name) = match word.strip_prefix("no") {
+                Some(name) if !sources.options.iter().any(|option| option.starts_with(word)) => ("no", name),
+                _ => ("", word),
+            };
+            sources.options
+                .iter()
+                .filter(|option| option.starts_with(name))
+                .map(|option| format!("{}{}", negation, option))
+                .collect()
+        },
+        Some("colo" | "colorscheme") => colorscheme_names().into_iter().filter(|name| name.starts_with(word)).collect(),
+        _ => vec![],
+    };
+    (start, candidates)
+}
+
+fn complete_path(word: &str) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+    let entries = match fs::read_dir(if dir.is_empty() { "." } else { dir }) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut paths: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false);
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_completed_escaped() {
+        let dir = std::env::temp_dir().join(format!("rime-complete-{}", std::process::id()));
+        fs::create_dir_all(dir.join("my dir")).unwrap();
+        fs::write(dir.join("my file"), "").unwrap();
+        let dir = escape_arg(&dir.to_string_lossy());
+        let sources = CompletionSources { buffers: vec![], options: vec![], commands: vec![] };
+        let (start, found) = candidates(&format!("e {}/my", dir), &sources);
+        assert_eq!((start, found), (2, vec![format!("{}/my\\ dir/", dir), format!("{}/my\\ file", dir)]));
+        let word = format!("{}/my\\ f", dir);
+        let line = format!("rename {}/my\\ dir/ {}", dir, word);
+        let (start, found) = candidates(&line, &sources);
+        assert_eq!((&line[start..], found), (word.as_str(), vec![format!("{}/my\\ file", dir)]));
+        fs::remove_dir_all(split_args(&dir).pop().unwrap()).unwrap();
+    }
+}
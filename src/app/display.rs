@@ -0,0 +1,131 @@
+use ratatui::{style::Style, text::{Line, Span}};
+use unicode_width::UnicodeWidthChar;
+
+#[derive(Clone, Copy, Default)]
+pub struct ListChars {
+    pub tab: Option<(char, char, Option<char>)>,
+    pub trail: Option<char>,
+    pub nbsp: Option<char>,
+    pub eol: Option<char>,
+}
+
+impl ListChars {
+    pub fn parse(value: &str) -> Option<ListChars> {
+        let mut list = ListChars::default();
+        for item in value.split(',').filter(|item| !item.is_empty()) {
+            let (name, chars) = item.split_once(':')?;
+            let chars: Vec<char> = chars.chars().collect();
+            match (name, chars.as_slice()) {
+                ("tab", &[first, fill]) => list.tab = Some((first, fill, None)),
+                ("tab", &[first, fill, last]) => list.tab = Some((first, fill, Some(last))),
+                ("trail", &[c]) => list.trail = Some(c),
+                ("nbsp", &[c]) => list.nbsp = Some(c),
+                ("eol", &[c]) => list.eol = Some(c),
+                _ => return None,
+            }
+        }
+        Some(list)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CharDisplay {
+    pub tabstop: usize,
+    pub list: Option<ListChars>,
+}
+
+impl Default for CharDisplay {
+    fn default() -> CharDisplay {
+        CharDisplay { tabstop: 8, list: None }
+    }
+}
+
+fn control(c: char) -> Option<String> {
+    match c as u32 {
+        0..=0x1f => Some(format!("^{}", (c as u8 + b'@') as char)),
+        0x7f => Some("^?".to_owned()),
+        0x80..=0x9f => Some(format!("<{:02x}>", c as u32)),
+        _ => None,
+    }
+}
+
+impl CharDisplay {
+    fn expand_tabs(&self) -> bool {
+        self.list.is_none_or(|list| list.tab.is_some())
+    }
+
+    pub fn width(&self, c: char, column: usize) -> usize {
+        match c {
+            '\t' if self.expand_tabs() => self.tabstop.max(1) - column % self.tabstop.max(1),
+            c => match control(c) {
+                Some(shown) => shown.len(),
+                None => c.width().unwrap_or(0),
+            },
+        }
+    }
+
+    pub fn column(&self, line: &str, byte: usize) -> usize {
+        line[..byte].chars().fold(0, |column, c| column + self.width(c, column))
+    }
+
+    pub fn byte_at(&self, line: &str, column: usize) -> usize {
+        let mut used = 0;
+        for (i, c) in line.char_indices() {
+            used += self.width(c, used);
+            if used > column {
+                return i;
+            }
+        }
+        line.len()
+    }
+
+    pub fn render<'a>(&self, line: &str, row: &Line<'a>, start: usize, last: bool, special: Style, non_text: Style) -> Line<'a> {
+        let trail = line.trim_end_matches(' ').len();
+        let mut column = self.column(line, start);
+        let mut byte = start;
+        let mut spans = vec![];
+        for span in &row.spans {
+            let mut text = String::new();
+            for c in span.content.chars() {
+                let width = self.width(c, column);
+                let shown = match (c, self.list) {
+                    ('\t', Some(ListChars { tab: Some((first, fill, last)), .. })) => Some(match last {
+                        Some(last) if width > 1 => format!("{}{}{}", first, fill.to_string().repeat(width - 2), last),
+                        Some(last) => last.to_string(),
+                        None => format!("{}{}", first, fill.to_string().repeat(width - 1)),
+                    }),
+                    ('\t', _) if self.expand_tabs() => {
+                        text.push_str(&" ".repeat(width));
+                        None
+                    },
+                    (' ', Some(ListChars { trail: Some(trail_char), .. })) if byte >= trail => Some(trail_char.to_string()),
+                    ('\u{a0}' | '\u{202f}', Some(ListChars { nbsp: Some(nbsp), .. })) => Some(nbsp.to_string()),
+                    (c, _) => match control(c) {
+                        Some(shown) => Some(shown),
+                        None => {
+                            text.push(c);
+                            None
+                        },
+                    },
+                };
+                if let Some(shown) = shown {
+                    if !text.is_empty() {
+                        spans.push(Span::styled(std::mem::take(&mut text), span.style));
+                    }
+                    spans.push(Span::styled(shown, special));
+                }
+                column += width;
+                byte += c.len_utf8();
+            }
+            if !text.is_empty() {
+                spans.push(Span::styled(text, span.style));
+            }
+        }
+        if last && let Some(ListChars { eol: Some(eol), .. }) = self.list {
+            spans.push(Span::styled(eol.to_string(), non_text));
+        }
+        let mut rendered = Line::from(spans);
+        rendered.style = row.style;
+        rendered
+    }
+}
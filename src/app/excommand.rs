@@ -0,0 +1,487 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::{App, CurrentEditing};
+
+pub struct Lines {
+    pub lines: Vec<String>,
+    trailing_newline: bool,
+}
+
+impl Lines {
+    pub fn new(text: &str) -> Lines {
+        let trailing_newline = text.ends_with('\n');
+        let body = if trailing_newline { &text[..text.len() - 1] } else { text };
+        Lines {
+            lines: body.split('\n').map(|line| line.to_owned()).collect(),
+            trailing_newline,
+        }
+    }
+
+    pub fn join(&self) -> String {
+        let mut text = self.lines.join("\n");
+        if self.trailing_newline {
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn start(&self, line: usize) -> usize {
+        self.lines[..line.min(self.lines.len())].iter().map(|line| line.len() + 1).sum()
+    }
+
+    fn remove(&mut self, start: usize, end: usize) {
+        self.lines.drain(start..=end);
+        if self.lines.is_empty() {
+            self.lines.push(String::new());
+            self.trailing_newline = false;
+        }
+    }
+}
+
+pub fn line_of(text: &str, cursor: usize) -> usize {
+    text[..cursor.min(text.len())].matches('\n').count()
+}
+
+fn parse_address(s: &str, current: usize, last: usize) -> Result<(Option<usize>, &str), String> {
+    let mut s = s.trim_start();
+    let mut address = match s.chars().next() {
+        Some('.') => {
+            s = &s[1..];
+            Some(current + 1)
+        },
+        Some('$') => {
+            s = &s[1..];
+            Some(last)
+        },
+        Some(c) if c.is_ascii_digit() => {
+            let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let number = s[..end].parse().map_err(|_| "E16: Invalid range".to_owned())?;
+            s = &s[end..];
+            Some(number)
+        },
+        _ => None,
+    };
+    while let Some(sign @ ('+' | '-')) = s.chars().next() {
+        s = &s[1..];
+        let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let offset: usize = if end == 0 { 1 } else { s[..end].parse().map_err(|_| "E16: Invalid range".to_owned())? };
+        s = &s[end..];
+        let base = address.unwrap_or(current + 1);
+        address = Some(if sign == '+' {
+            base + offset
+        } else {
+            base.checked_sub(offset).ok_or("E16: Invalid range".to_owned())?
+        });
+    }
+    if let Some(address) = address && address > last {
+        return Err("E16: Invalid range".to_owned());
+    }
+    Ok((address, s))
+}
+
+type Range = Option<(usize, usize)>;
+
+pub fn parse_range(s: &str, current: usize, last: usize) -> Result<(Range, &str), String> {
+    let s = s.trim_start();
+    if let Some(rest) = s.strip_prefix('%') {
+        return Ok((Some((1, last)), rest));
+    }
+    let (start, rest) = parse_address(s, current, last)?;
+    let (end, rest) = match rest.strip_prefix(',') {
+        Some(rest) => {
+            let (end, rest) = parse_address(rest, current, last)?;
+            (end.or(start), rest)
+        },
+        None => (start, rest),
+    };
+    match (start, end) {
+        (Some(start), Some(end)) if start > end => Ok((Some((end, start)), rest)),
+        (Some(start), Some(end)) => Ok((Some((start, end)), rest)),
+        (None, Some(end)) => Ok((Some((current + 1, end)), rest)),
+        _ => Ok((None, rest)),
+    }
+}
+
+pub fn split_pattern(s: &str) -> Result<(char, String, &str), String> {
+    let delimiter = match s.chars().next() {
+        Some(c) if !c.is_alphanumeric() && !c.is_whitespace() && c != '\\' && c != '"' => c,
+        _ => return Err("E146: Regular expressions can't be delimited by letters".to_owned()),
+    };
+    let (pattern, rest) = split_until(&s[delimiter.len_utf8()..], delimiter);
+    Ok((delimiter, pattern, rest))
+}
+
+pub fn split_until(s: &str, delimiter: char) -> (String, &str) {
+    let mut part = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == delimiter {
+            return (part, &s[i + c.len_utf8()..]);
+        }
+        if c == '\\' && let Some((_, next)) = chars.next() {
+            if next != delimiter {
+                part.push('\\');
+            }
+            part.push(next);
+        } else {
+            part.push(c);
+        }
+    }
+    (part, "")
+}
+
+// Patterns are literal text: only a leading ^ and a trailing $ are special, everything else matches itself.
+pub fn find(line: &str, pattern: &str, from: usize, ignore_case: bool) -> Option<(usize, usize)> {
+    let (at_start, pattern) = match pattern.strip_prefix('^') {
+        Some(pattern) => (true, pattern),
+        None => (false, pattern),
+    };
+    let (at_end, pattern) = match pattern.strip_suffix('$') {
+        Some(stripped) if !stripped.ends_with('\\') => (true, stripped),
+        _ => (false, pattern),
+    };
+    let (line, pattern) = if ignore_case {
+        (line.to_ascii_lowercase(), pattern.to_ascii_lowercase())
+    } else {
+        (line.to_owned(), pattern.to_owned())
+    };
+    if from > line.len() {
+        return None;
+    }
+    let found = if at_start {
+        if from == 0 && line.starts_with(&pattern) { Some(0) } else { None }
+    } else if at_end {
+        if line.ends_with(&pattern) && line.len() - pattern.len() >= from { Some(line.len() - pattern.len()) } else { None }
+    } else {
+        line[from..].find(&pattern).map(|i| from + i)
+    };
+    match found {
+        Some(start) if at_end && start + pattern.len() != line.len() => None,
+        Some(start) => Some((start, start + pattern.len())),
+        None => None,
+    }
+}
+
+pub fn substitute(line: &str, pattern: &str, replacement: &str, global: bool, ignore_case: bool) -> Option<String> {
+    let mut result = String::new();
+    let mut from = 0;
+    let mut replaced = false;
+    while let Some((start, end)) = find(line, pattern, from, ignore_case) {
+        result.push_str(&line[from..start]);
+        let mut chars = replacement.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '&' => result.push_str(&line[start..end]),
+                '\\' => if let Some(next) = chars.next() {
+                    result.push(next);
+                },
+                c => result.push(c),
+            }
+        }
+        replaced = true;
+        from = end;
+        if start == end {
+            match line[end..].chars().next() {
+                Some(c) => {
+                    result.push(c);
+                    from += c.len_utf8();
+                },
+                None => break,
+            }
+        }
+        if !global {
+            break;
+        }
+    }
+    if replaced {
+        result.push_str(&line[from.min(line.len())..]);
+        Some(result)
+    } else {
+        None
+    }
+}
+
+pub const LINE_COMMANDS: [&str; 16] = [
+    "d", "de", "del", "delete", "m", "mo", "move", "s", "substitute",
+    "norm", "normal", "g", "global", "v", "vglobal", "p",
+];
+
+impl App {
+    fn lines(&self, index: usize) -> (Lines, usize) {
+        match self.files[index].undo_tree.show_current_node() {
+            Some((text, cursor)) => (Lines::new(&text), line_of(&text, cursor)),
+            None => (Lines::new(""), 0),
+        }
+    }
+
+    fn set_lines(&mut self, index: usize, lines: &Lines, cursor_line: usize) {
+        let cursor = lines.start(cursor_line);
+        self.files[index].undo_tree.replace_text(lines.join(), cursor);
+    }
+
+    pub fn pattern_or_last(&self, pattern: String) -> Result<String, String> {
+        match (pattern.is_empty(), self.last_search.is_empty()) {
+            (false, _) => Ok(pattern),
+            (true, false) => Ok(self.last_search.clone()),
+            (true, true) => Err("E35: No previous regular expression".to_owned()),
+        }
+    }
+
+    pub fn ignore_case(&self, index: usize, pattern: &str) -> bool {
+        let local = Some(&self.files[index].local_options);
+        self.options.bool(local, "ignorecase")
+            && !(self.options.bool(local, "smartcase") && pattern.chars().any(|c| c.is_uppercase()))
+    }
+
+    pub fn execute_line_command(&mut self, index: usize, command: &str) -> Option<Result<(), String>> {
+        if self.files[index].large.is_some() {
+            return self.large_line_command(index, command);
+        }
+        if self.files[index].hex.is_some() {
+            let name = parse_range(command, 0, 1).map(|(_, rest)| rest.trim_start()).unwrap_or(command);
+            let name = &name[..name.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(name.len())];
+            return LINE_COMMANDS.contains(&name).then(|| Err("E21: Line commands are not available in hex mode (:hex to go back to text)".to_owned()));
+        }
+        let (lines, current) = self.lines(index);
+        let (range, rest) = match parse_range(command, current, lines.lines.len()) {
+            Ok(parsed) => parsed,
+            Err(error) => return Some(Err(error)),
+        };
+        let rest = rest.trim_start();
+        let name = &rest[..rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len())];
+        if name.is_empty() && let Some((_, end)) = range {
+            self.files[index].undo_tree.set_cursor(lines.start(end.saturating_sub(1)));
+            return Some(Ok(()));
+        }
+        if !LINE_COMMANDS.contains(&name) {
+            return if range.is_some() { Some(Err("E481: No range allowed".to_owned())) } else { None };
+        }
+        let checkpoint = self.files[index].undo_tree.current.clone();
+        let result = self.run_line_command(index, command, None);
+        self.current_editing = CurrentEditing::Selecting;
+        self.files[index].undo_tree.squash(checkpoint);
+        Some(result)
+    }
+
+    fn run_line_command(&mut self, index: usize, command: &str, mut marks: Option<&mut Vec<bool>>) -> Result<(), String> {
+        let (mut lines, current) = self.lines(index);
+        let last = lines.lines.len();
+        let (range, rest) = parse_range(command, current, last)?;
+        let rest = rest.trim_start();
+        let name_len = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let (name, args) = rest.split_at(name_len);
+        let (start, end) = match (range, name) {
+            (Some((0, _)), _) => return Err("E16: Invalid range".to_owned()),
+            (Some((start, end)), _) => (start - 1, end - 1),
+            (None, "g" | "global" | "v" | "vglobal") => (0, last - 1),
+            (None, _) => (current, current),
+        };
+        match name {
+            "d" | "de" | "del" | "delete" => {
+                lines.remove(start, end);
+                self.files[index].signs.shift(start, end + 1 - start, 0);
+                self.files[index].signs.clamp(lines.lines.len());
+                if let Some(marks) = marks.as_deref_mut() {
+                    marks.drain(start..=end);
+                }
+                let cursor_line = start.min(lines.lines.len() - 1);
+                self.set_lines(index, &lines, cursor_line);
+            },
+            "m" | "mo" | "move" => {
+                let (destination, _) = parse_address(args, current, last)?;
+                let destination = destination.ok_or("E14: Invalid address".to_owned())?;
+                if destination > start && destination <= end {
+                    return Err("E134: Cannot move a range of lines into itself".to_owned());
+                }
+                let moved: Vec<String> = lines.lines.drain(start..=end).collect();
+                let count = moved.len();
+                let insert_at = if destination > end { destination - count } else { destination };
+                lines.lines.splice(insert_at..insert_at, moved);
+                if let Some(marks) = marks.as_deref_mut() {
+                    let moved: Vec<bool> = marks.drain(start..=end).collect();
+                    marks.splice(insert_at..insert_at, moved);
+                }
+                self.files[index].signs.move_lines(start, end, insert_at);
+                self.set_lines(index, &lines, insert_at + count - 1);
+            },
+            "s" | "substitute" => {
+                let (delimiter, pattern, rest) = split_pattern(args)?;
+                let pattern = self.pattern_or_last(pattern)?;
+                let (replacement, flags) = split_until(rest, delimiter);
+                let ignore_case = if flags.contains('I') {
+                    false
+                } else {
+                    flags.contains('i') || self.ignore_case(index, &pattern)
+                };
+                let mut last_changed = None;
+                for line in start..=end {
+                    if let Some(new) = substitute(&lines.lines[line], &pattern, &replacement, flags.contains('g'), ignore_case) {
+                        lines.lines[line] = new;
+                        last_changed = Some(line);
+                    }
+                }
+                match last_changed {
+                    Some(line) => self.set_lines(index, &lines, line),
+                    None if marks.is_none() => return Err(format!("E486: Pattern not found: {}", pattern)),
+                    None => (),
+                }
+            },
+            "norm" | "normal" => {
+                let keys = args.strip_prefix(' ').unwrap_or(args).to_owned();
+                let mut line = start;
+                let mut end = end as isize;
+                while line as isize <= end {
+                    let (lines, _) = self.lines(index);
+                    if line >= lines.lines.len() {
+                        break;
+                    }
+                    let before = lines.lines.len() as isize;
+                    self.files[index].undo_tree.set_cursor(lines.start(line));
+                    self.current_editing = CurrentEditing::Selecting;
+                    for c in keys.chars() {
+                        self.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+                    }
+                    if !matches!(self.current_editing, CurrentEditing::Selecting) {
+                        self.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+                    }
+                    let delta = self.lines(index).0.lines.len() as isize - before;
+                    if let Some(marks) = marks.as_deref_mut() {
+                        if delta > 0 {
+                            let at = (line + 1).min(marks.len());
+                            marks.splice(at..at, std::iter::repeat_n(false, delta as usize));
+                        } else if delta < 0 {
+                            let from = line.min(marks.len());
+                            let to = (from + (-delta) as usize).min(marks.len());
+                            marks.drain(from..to);
+                        }
+                    }
+                    end += delta;
+                    line = (line as isize + 1 + delta).max(line as isize) as usize;
+                }
+            },
+            "p" => {
+                for line in start..=end {
+                    self.info(format!("{:>4} {}", line + 1, lines.lines[line]));
+                }
+                self.show_messages = true;
+            },
+            "g" | "global" | "v" | "vglobal" => {
+                if marks.is_some() {
+                    return Err("E147: Cannot do :global recursive".to_owned());
+                }
+                let (invert, args) = match args.strip_prefix('!') {
+                    Some(args) => (true, args),
+                    None => (name.starts_with('v'), args),
+                };
+                let (_, pattern, subcommand) = split_pattern(args)?;
+                let pattern = self.pattern_or_last(pattern)?;
+                let subcommand = if subcommand.trim().is_empty() { "p" } else { subcommand };
+                let ignore_case = self.ignore_case(index, &pattern);
+                let mut marks: Vec<bool> = lines.lines
+                    .iter()
+                    .enumerate()
+                    .map(|(i, line)| i >= start && i <= end && find(line, &pattern, 0, ignore_case).is_some() != invert)
+                    .collect();
+                if !marks.contains(&true) {
+                    return Err(format!("E486: Pattern not found: {}", pattern));
+                }
+                while let Some(line) = marks.iter().position(|marked| *marked) {
+                    marks[line] = false;
+                    let (lines, _) = self.lines(index);
+                    self.files[index].undo_tree.set_cursor(lines.start(line));
+                    self.run_line_command(index, subcommand, Some(&mut marks))?;
+                }
+            },
+            _ => return Err(format!("E492: Not an editor command: {}", command.trim())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{CurrentScreenMode, File};
+
+    fn open(text: &str, line: usize) -> App {
+        let mut app = App::new();
+        let mut file = File::new("lines".to_owned());
+        file.undo_tree.replace_text(text.to_owned(), Lines::new(text).start(line));
+        app.files.push(file);
+        app.current_screen = CurrentScreenMode::File(0);
+        app
+    }
+
+    fn run(app: &mut App, command: &str) -> Result<(), String> {
+        app.execute_line_command(0, command).unwrap()
+    }
+
+    fn text(app: &App) -> String {
+        app.files[0].text()
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(parse_range(".d", 2, 5), Ok((Some((3, 3)), "d")));
+        assert_eq!(parse_range("$", 2, 5), Ok((Some((5, 5)), "")));
+        assert_eq!(parse_range("%s/a/b/", 2, 5), Ok((Some((1, 5)), "s/a/b/")));
+        assert_eq!(parse_range("2,4m0", 0, 5), Ok((Some((2, 4)), "m0")));
+        assert_eq!(parse_range("4,2", 0, 5), Ok((Some((2, 4)), "")));
+        assert_eq!(parse_range(".+1,$-1", 0, 5), Ok((Some((2, 4)), "")));
+        assert_eq!(parse_range("+,++", 0, 5), Ok((Some((2, 3)), "")));
+        assert_eq!(parse_range("-2", 3, 5), Ok((Some((2, 2)), "")));
+        assert_eq!(parse_range(",3", 0, 5), Ok((Some((1, 3)), "")));
+        assert_eq!(parse_range("d", 0, 5), Ok((None, "d")));
+        assert_eq!(parse_range("'a,'bd", 0, 5), Ok((None, "'a,'bd")));
+        assert!(parse_range("6", 0, 5).is_err());
+        assert!(parse_range("$+1", 0, 5).is_err());
+        assert!(parse_range(".-2", 0, 5).is_err());
+    }
+
+    #[test]
+    fn global_delete() {
+        let mut app = open("a1\nb\na2\na3\nc\n", 0);
+        run(&mut app, "g/a/d").unwrap();
+        assert_eq!(text(&app), "b\nc\n");
+        let mut app = open("a1\nb\na2\na3\nc\n", 0);
+        run(&mut app, "2,4g/a/d").unwrap();
+        assert_eq!(text(&app), "a1\nb\nc\n");
+        let mut app = open("a\na\n", 0);
+        run(&mut app, "g/a/d").unwrap();
+        assert_eq!(text(&app), "");
+        assert!(run(&mut open("a\n", 0), "g/z/d").is_err());
+    }
+
+    #[test]
+    fn vglobal() {
+        let mut app = open("a1\nb\na2\nc\n", 0);
+        run(&mut app, "v/a/d").unwrap();
+        assert_eq!(text(&app), "a1\na2\n");
+        let mut app = open("a1\nb\na2\nc\n", 0);
+        run(&mut app, "g!/a/s/$/!/").unwrap();
+        assert_eq!(text(&app), "a1\nb!\na2\nc!\n");
+        let mut app = open("a\nb\nc\nd\n", 0);
+        run(&mut app, "1,3v/b/d").unwrap();
+        assert_eq!(text(&app), "b\nd\n");
+    }
+
+    #[test]
+    fn move_lines() {
+        let mut app = open("1\n2\n3\n4\n5\n", 0);
+        run(&mut app, "2,3m$").unwrap();
+        assert_eq!(text(&app), "1\n4\n5\n2\n3\n");
+        let mut app = open("1\n2\n3\n4\n5\n", 0);
+        run(&mut app, "4,5m0").unwrap();
+        assert_eq!(text(&app), "4\n5\n1\n2\n3\n");
+        let mut app = open("1\n2\n3\n4\n5\n", 0);
+        run(&mut app, "2,3m3").unwrap();
+        assert_eq!(text(&app), "1\n2\n3\n4\n5\n");
+        assert!(run(&mut app, "2,4m3").is_err());
+        let mut app = open("1\n2\n3\n", 2);
+        run(&mut app, "m0").unwrap();
+        assert_eq!(text(&app), "3\n1\n2\n");
+        let mut app = open("x1\ny\nx2\nz\n", 0);
+        run(&mut app, "g/x/m0").unwrap();
+        assert_eq!(text(&app), "x2\nx1\ny\nz\n");
+    }
+}
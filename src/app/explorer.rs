@@ -0,0 +1,292 @@
+use std::{
+    cell::Cell,
+    fs, io,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::{
+    options::escape_arg,
+    prompt::{Prompt, PromptAction},
+    App, CurrentEditing, CurrentScreenMode, File, LineEditor,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    Parent,
+    Directory,
+    File,
+    Executable,
+    Symlink,
+}
+
+impl EntryKind {
+    pub fn icon(self) -> &'static str {
+        match self {
+            EntryKind::Parent => "^",
+            EntryKind::Directory => "+",
+            EntryKind::File => "-",
+            EntryKind::Executable => "*",
+            EntryKind::Symlink => "@",
+        }
+    }
+}
+
+pub struct Entry {
+    pub name: String,
+    pub kind: EntryKind,
+}
+
+pub struct Explorer {
+    pub dir: PathBuf,
+    pub entries: Vec<Entry>,
+    pub selected: usize,
+    pub show_hidden: bool,
+    pub top: Cell<usize>,
+}
+
+impl Explorer {
+    pub fn new(dir: &Path, show_hidden: bool) -> io::Result<Explorer> {
+        let mut explorer = Explorer {
+            dir: fs::canonicalize(dir)?,
+            entries: vec![],
+            selected: 0,
+            show_hidden,
+            top: Cell::new(0),
+        };
+        explorer.refresh()?;
+        Ok(explorer)
+    }
+
+    pub fn refresh(&mut self) -> io::Result<()> {
+        let selected = self.entries.get(self.selected).map(|entry| entry.name.clone());
+        let mut entries = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !self.show_hidden && name.starts_with('.') {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            let kind = if file_type.is_symlink() {
+                EntryKind::Symlink
+            } else if file_type.is_dir() {
+                EntryKind::Directory
+            } else if entry.metadata().is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0) {
+                EntryKind::Executable
+            } else {
+                EntryKind::File
+            };
+            entries.push(Entry { name, kind });
+        }
+        entries.sort_by(|a, b| (a.kind != EntryKind::Directory, &a.name).cmp(&(b.kind != EntryKind::Directory, &b.name)));
+        if self.dir.parent().is_some() {
+            entries.insert(0, Entry { name: "..".to_owned(), kind: EntryKind::Parent });
+        }
+        self.entries = entries;
+        self.selected = selected
+            .and_then(|name| self.entries.iter().position(|entry| entry.name == name))
+            .unwrap_or(self.selected)
+            .min(self.entries.len().saturating_sub(1));
+        Ok(())
+    }
+
+    pub fn selected_path(&self) -> Option<PathBuf> {
+        self.entries.get(self.selected).map(|entry| self.dir.join(&entry.name))
+    }
+
+    fn move_to(&mut self, selected: usize) {
+        self.selected = selected.min(self.entries.len().saturating_sub(1));
+    }
+}
+
+pub fn display_path(path: &Path) -> String {
+    match std::env::current_dir().ok().and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf)) {
+        Some(relative) if relative.as_os_str().is_empty() => ".".to_owned(),
+        Some(relative) => relative.display().to_string(),
+        None => path.display().to_string(),
+    }
+}
+
+impl App {
+    pub fn open_explorer(&mut self, name: &str) {
+        match Explorer::new(Path::new(name), false) {
+            Ok(explorer) => {
+                let mut file = File::new(display_path(&explorer.dir));
+                file.explorer = Some(explorer);
+                self.files.push(file);
+                self.current_screen = CurrentScreenMode::File(self.files.len() - 1);
+            },
+            Err(error) => self.error(format!("\"{}\" {}", name, error)),
+        }
+    }
+
+    fn change_directory(&mut self, index: usize, dir: &Path) {
+        let Some(explorer) = self.files[index].explorer.as_ref() else { return };
+        let previous = explorer.dir.file_name().map(|name| name.to_string_lossy().into_owned());
+        match Explorer::new(dir, explorer.show_hidden) {
+            Ok(mut explorer) => {
+                if let Some(previous) = previous && let Some(position) = explorer.entries.iter().position(|entry| entry.name == previous) {
+                    explorer.selected = position;
+                }
+                self.files[index].name = display_path(&explorer.dir);
+                self.files[index].explorer = Some(explorer);
+            },
+            Err(error) => self.error(format!("\"{}\" {}", dir.display(), error)),
+        }
+    }
+
+    pub fn refresh_explorer(&mut self, index: usize) {
+        if let Some(explorer) = self.files[index].explorer.as_mut() && let Err(error) = explorer.refresh() {
+            let message = format!("\"{}\" {}", explorer.dir.display(), error);
+            self.error(message);
+        }
+    }
+
+    pub fn explorer_key(&mut self, index: usize, key: KeyEvent) {
+        let Some(explorer) = self.files[index].explorer.as_mut() else { return };
+        let dir = display_path(&explorer.dir);
+        let selected = explorer.entries.get(explorer.selected).filter(|entry| entry.kind != EntryKind::Parent).map(|entry| entry.name.clone());
+        let prefill = |text: String| {
+            let mut line = LineEditor::new();
+            line.set(text);
+            CurrentEditing::Command(line)
+        };
+        match key.code {
+            KeyCode::Char('i') if key.modifiers == KeyModifiers::ALT => explorer.move_to(explorer.selected.saturating_sub(1)),
+            KeyCode::Char('k') if key.modifiers == KeyModifiers::ALT => explorer.move_to(explorer.selected + 1),
+            KeyCode::Up => explorer.move_to(explorer.selected.saturating_sub(1)),
+            KeyCode::Down => explorer.move_to(explorer.selected + 1),
+            KeyCode::Home => explorer.move_to(0),
+            KeyCode::End | KeyCode::Char('G') => explorer.move_to(usize::MAX),
+            KeyCode::Enter | KeyCode::Char('o') => self.explorer_open(index),
+            KeyCode::Backspace | KeyCode::Char('-') => if let Some(parent) = explorer.dir.parent() {
+                let parent = parent.to_path_buf();
+                self.change_directory(index, &parent);
+            },
+            KeyCode::Char('.') => {
+                explorer.show_hidden = !explorer.show_hidden;
+                self.refresh_explorer(index);
+            },
+            KeyCode::Char('%') => self.current_editing = prefill(format!("tabnew {}/", escape_arg(&dir))),
+            KeyCode::Char('d') => self.current_editing = prefill(format!("mkdir {}/", escape_arg(&dir))),
+            KeyCode::Char('R') => if let Some(name) = selected {
+                let path = escape_arg(&format!("{}/{}", dir, name));
+                self.current_editing = prefill(format!("rename {0} {0}", path));
+            },
+            KeyCode::Char('D') => if let Some(name) = selected {
+                self.current_editing = CurrentEditing::Prompt(Prompt {
+                    message: format!("Delete \"{}\"?", name),
+                    choices: vec![
+                        ('y', "Yes", PromptAction::DeletePath(explorer.dir.join(&name))),
+                        ('n', "No", PromptAction::Cancel),
+                    ],
+                    default: 1,
+                });
+            },
+            KeyCode::Char(':') => self.current_editing = CurrentEditing::Command(LineEditor::new()),
+            KeyCode::Char('/') => self.current_editing = CurrentEditing::Search(LineEditor::new()),
+            KeyCode::Char('n') => self.search(String::new(), true),
+            KeyCode::Char('N') => self.search(String::new(), false),
+            KeyCode::Char(c) => self.current_editing = CurrentEditing::Listening(c),
+            _ => (),
+        }
+    }
+
+    fn explorer_open(&mut self, index: usize) {
+        let Some(explorer) = self.files[index].explorer.as_ref() else { return };
+        let Some(path) = explorer.selected_path() else { return };
+        if path.is_dir() {
+            self.change_directory(index, &path);
+        } else {
+            let name = display_path(&path);
+            if !self.switch_to(&name) {
+                self.open_path(name, None);
+            }
+        }
+    }
+
+    pub fn explorer_search(&mut self, index: usize, pattern: &str, forward: bool) {
+        let Some(explorer) = self.files[index].explorer.as_mut() else { return };
+        let len = explorer.entries.len();
+        let found = (1..=len)
+            .map(|step| if forward { (explorer.selected + step) % len } else { (explorer.selected + len - step) % len })
+            .find(|&i| explorer.entries[i].name.contains(pattern));
+        if let Some(found) = found {
+            explorer.selected = found;
+        }
+    }
+
+    pub fn refresh_explorers(&mut self) {
+        for index in 0..self.files.len() {
+            self.refresh_explorer(index);
+        }
+    }
+
+    pub fn make_directory(&mut self, name: &str) {
+        if name.is_empty() {
+            self.error("E471: Argument required".to_owned());
+            return;
+        }
+        let path = Path::new(name);
+        match fs::create_dir_all(path) {
+            Ok(_) => self.refresh_explorers(),
+            Err(error) => self.error(format!("\"{}\" {}", path.display(), error)),
+        }
+    }
+
+    pub fn rename_path(&mut self, from: &str, to: &str) {
+        if from.is_empty() || to.is_empty() {
+            self.error("E471: Argument required".to_owned());
+            return;
+        }
+        let from = PathBuf::from(from);
+        let mut to = PathBuf::from(to);
+        if to.is_dir() && let Some(name) = from.file_name() {
+            to.push(name);
+        }
+        if to.exists() {
+            self.error(format!("E13: File exists: \"{}\"", to.display()));
+            return;
+        }
+        let old = fs::canonicalize(&from).unwrap_or_else(|_| from.clone());
+        let moved: Vec<(usize, PathBuf)> = self.files
+            .iter()
+            .enumerate()
+            .filter(|(_, file)| file.explorer.is_none())
+            .filter_map(|(index, file)| {
+                let path = fs::canonicalize(&file.name).ok()?;
+                Some((index, path.strip_prefix(&old).ok()?.to_path_buf()))
+            })
+            .collect();
+        if let Err(error) = fs::rename(&from, &to) {
+            self.error(format!("\"{}\" {}", from.display(), error));
+            return;
+        }
+        let new = fs::canonicalize(&to).unwrap_or(to);
+        for (index, rest) in moved {
+            let path = if rest.as_os_str().is_empty() { new.clone() } else { new.join(rest) };
+            self.files[index].name = display_path(&path);
+            if let Some(swap) = self.files[index].swap.take() {
+                let swap = swap.strip_prefix(&old).map_or(swap.clone(), |rest| new.join(rest));
+                self.move_swap(index, &swap);
+            }
+        }
+        self.write_swap_files();
+        self.refresh_explorers();
+    }
+
+    pub fn delete_path(&mut self, path: &Path) {
+        let result = match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir(path),
+            Ok(_) => fs::remove_file(path),
+            Err(error) => Err(error),
+        };
+        match result {
+            Ok(_) => self.refresh_explorers(),
+            Err(error) => self.error(format!("\"{}\" {}", path.display(), error)),
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use std::{
+    fs,
+    io::{self, Read, Seek, Write},
+    os::unix::{fs::{MetadataExt, OpenOptionsExt}, io::AsRawFd},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct DiskState {
+    mtime: SystemTime,
+    size: u64,
+    inode: u64,
+}
+
+pub fn disk_state(path: &Path) -> Option<DiskState> {
+    let metadata = fs::metadata(path).ok()?;
+    Some(DiskState {
+        mtime: metadata.modified().ok()?,
+        size: metadata.len(),
+        inode: metadata.ino(),
+    })
+}
+
+pub fn is_writable(path: &Path) -> bool {
+    match std::ffi::CString::new(path.as_os_str().as_encoded_bytes()) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 },
+        Err(_) => false,
+    }
+}
+
+pub struct TextFormat {
+    pub encoding: String,
+    pub fileformat: String,
+    pub bomb: bool,
+    pub endofline: bool,
+}
+
+impl Default for TextFormat {
+    fn default() -> TextFormat {
+        TextFormat {
+            encoding: "utf-8".to_owned(),
+            fileformat: "unix".to_owned(),
+            bomb: false,
+            endofline: true,
+        }
+    }
+}
+
+pub fn encoding_name(name: &str) -> Option<&'static str> {
+    match name.to_ascii_lowercase().as_str() {
+        "utf-8" | "utf8" => Some("utf-8"),
+        "utf-16" | "utf-16be" | "ucs-2" | "ucs-2be" => Some("utf-16"),
+        "utf-16le" | "ucs-2le" => Some("utf-16le"),
+        "latin1" | "latin-1" | "iso-8859-1" => Some("latin1"),
+        _ => None,
+    }
+}
+
+fn bom(encoding: &str) -> &'static [u8] {
+    match encoding {
+        "utf-8" => &[0xef, 0xbb, 0xbf],
+        "utf-16" => &[0xfe, 0xff],
+        "utf-16le" => &[0xff, 0xfe],
+        _ => &[],
+    }
+}
+
+fn detect_encoding(bytes: &[u8]) -> (&'static str, bool) {
+    for encoding in ["utf-8", "utf-16le", "utf-16"] {
+        if bytes.starts_with(bom(encoding)) {
+            return (encoding, true);
+        }
+    }
+    let utf8 = std::str::from_utf8(bytes).is_ok();
+    if utf8 && !bytes.contains(&0) {
+        return ("utf-8", false);
+    }
+    if bytes.len().is_multiple_of(2) {
+        let zeros = |parity| bytes.iter().skip(parity).step_by(2).filter(|&&b| b == 0).count();
+        let (even, odd) = (zeros(0), zeros(1));
+        let units = bytes.len() / 2;
+        if odd * 4 > units && even * 4 < odd && decode(bytes, "utf-16le").is_some() {
+            return ("utf-16le", false);
+        }
+        if even * 4 > units && odd * 4 < even && decode(bytes, "utf-16").is_some() {
+            return ("utf-16", false);
+        }
+    }
+    (if utf8 { "utf-8" } else { "latin1" }, false)
+}
+
+fn decode(bytes: &[u8], encoding: &str) -> Option<String> {
+    match encoding {
+        "utf-16" | "utf-16le" => {
+            if !bytes.len().is_multiple_of(2) {
+                return None;
+            }
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|pair| if encoding == "utf-16" { u16::from_be_bytes([pair[0], pair[1]]) } else { u16::from_le_bytes([pair[0], pair[1]]) })
+                .collect();
+            String::from_utf16(&units).ok()
+        },
+        "latin1" => Some(bytes.iter().map(|&b| b as char).collect()),
+        _ => String::from_utf8(bytes.to_vec()).ok(),
+    }
+}
+
+fn encode(text: &str, encoding: &str) -> Option<Vec<u8>> {
+    match encoding {
+        "utf-16" => Some(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+        "utf-16le" => Some(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+        "latin1" => text.chars().map(|c| u8::try_from(c as u32).ok()).collect(),
+        _ => Some(text.as_bytes().to_vec()),
+    }
+}
+
+pub fn read_bytes(mut reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub fn read_text(reader: impl Read, encoding: Option<&str>) -> io::Result<(String, TextFormat)> {
+    decode_bytes(&read_bytes(reader)?, encoding)
+}
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(8192)];
+    if sample.is_empty() || detect_encoding(bytes).0.starts_with("utf-16") {
+        return false;
+    }
+    let control = sample.iter().filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)).count();
+    sample.contains(&0) || control * 10 > sample.len()
+}
+
+pub fn decode_bytes(bytes: &[u8], encoding: Option<&str>) -> io::Result<(String, TextFormat)> {
+    let (encoding, bomb) = match encoding {
+        Some(encoding) => (encoding, !bom(encoding).is_empty() && bytes.starts_with(bom(encoding))),
+        None => detect_encoding(bytes),
+    };
+    let content = if bomb { &bytes[bom(encoding).len()..] } else { bytes };
+    match decode(content, encoding) {
+        Some(text) => Ok(decode_text(text, encoding, bomb)),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, format!("Illegal byte sequence for {}", encoding))),
+    }
+}
+
+fn decode_text(mut text: String, encoding: &str, bomb: bool) -> (String, TextFormat) {
+    let newlines = text.matches('\n').count();
+    let crlf = text.matches("\r\n").count();
+    let fileformat = if newlines > 0 && crlf == newlines {
+        text = text.replace("\r\n", "\n");
+        "dos"
+    } else if newlines == 0 && text.contains('\r') {
+        text = text.replace('\r', "\n");
+        "mac"
+    } else {
+        "unix"
+    };
+    let endofline = text.ends_with('\n');
+    if text.ends_with('\n') {
+        text.pop();
+    }
+    (text, TextFormat { encoding: encoding.to_owned(), fileformat: fileformat.to_owned(), bomb, endofline })
+}
+
+pub fn encode_text(text: &str, format: &TextFormat) -> io::Result<Vec<u8>> {
+    let newline = match format.fileformat.as_str() {
+        "dos" => "\r\n",
+        "mac" => "\r",
+        _ => "\n",
+    };
+    let mut data = text.replace('\n', newline);
+    if format.endofline {
+        data.push_str(newline);
+    }
+    let mut bytes = if format.bomb { bom(&format.encoding).to_vec() } else { vec![] };
+    match encode(&data, &format.encoding) {
+        Some(encoded) => bytes.extend(encoded),
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("E513: Write error, conversion to {} failed (set fileencoding=utf-8 to override)", format.encoding))),
+    }
+    Ok(bytes)
+}
+
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    write_atomic_with(path, true, &mut |file| file.write_all(data))
+}
+
+pub fn write_atomic_with(path: &Path, in_place: bool, write: &mut dyn FnMut(&mut fs::File) -> io::Result<()>) -> io::Result<()> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let metadata = fs::metadata(&target).ok();
+    let write_in_place = |write: &mut dyn FnMut(&mut fs::File) -> io::Result<()>| match in_place {
+        true => write_in_place(&target, write),
+        false => Err(io::Error::other("the file is still being read and can't be written in place")),
+    };
+    if let Some(metadata) = &metadata && metadata.nlink() > 1 {
+        return write_in_place(write);
+    }
+    let temp = temp_path(&target);
+    match write_and_rename(&temp, &target, write, metadata.as_ref()) {
+        Ok(true) => Ok(()),
+        Ok(false) => {
+            let _ = fs::remove_file(&temp);
+            write_in_place(write)
+        },
+        Err(error) => {
+            let _ = fs::remove_file(&temp);
+            match error.raw_os_error() {
+                Some(libc::EXDEV | libc::EBUSY | libc::EPERM | libc::EACCES | libc::EROFS) if metadata.is_some() => write_in_place(write),
+                _ => Err(error),
+            }
+        },
+    }
+}
+
+fn temp_path(target: &Path) -> PathBuf {
+    let name = target.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    dir.join(format!(".{}.{}.rime-tmp", name, std::process::id()))
+}
+
+fn write_and_rename(temp: &Path, target: &Path, write: &mut dyn FnMut(&mut fs::File) -> io::Result<()>, metadata: Option<&fs::Metadata>) -> io::Result<bool> {
+    let mode = metadata.map(|metadata| metadata.mode() & 0o7777).unwrap_or(0o666);
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(temp)?;
+    if let Some(metadata) = metadata {
+        let created = file.metadata()?;
+        if (created.uid(), created.gid()) != (metadata.uid(), metadata.gid())
+            && unsafe { libc::fchown(file.as_raw_fd(), metadata.uid(), metadata.gid()) } != 0 {
+            return Ok(false);
+        }
+        file.set_permissions(metadata.permissions())?;
+    }
+    write(&mut file)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(temp, target)?;
+    if let Some(dir) = target.parent() && let Ok(dir) = fs::File::open(if dir.as_os_str().is_empty() { Path::new(".") } else { dir }) {
+        let _ = dir.sync_all();
+    }
+    Ok(true)
+}
+
+fn write_in_place(target: &Path, write: &mut dyn FnMut(&mut fs::File) -> io::Result<()>) -> io::Result<()> {
+    let backup = backup_copy(target);
+    let mut file = fs::OpenOptions::new().write(true).open(target)?;
+    write(&mut file)?;
+    let len = file.stream_position()?;
+    file.set_len(len)?;
+    file.sync_all()?;
+    if let Some(backup) = backup {
+        let _ = fs::remove_file(backup);
+    }
+    Ok(())
+}
+
+fn backup_copy(target: &Path) -> Option<PathBuf> {
+    let mut source = fs::File::open(target).ok()?;
+    for n in 0..100 {
+        let mut backup = target.as_os_str().to_owned();
+        backup.push(format!(".{}", std::process::id()));
+        if n > 0 {
+            backup.push(format!(".{}", n));
+        }
+        backup.push("~");
+        let backup = PathBuf::from(backup);
+        let mut file = match fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(&backup) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(_) => return None,
+        };
+        if io::copy(&mut source, &mut file).and_then(|_| file.sync_all()).is_err() {
+            let _ = fs::remove_file(&backup);
+            return None;
+        }
+        return Some(backup);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(bytes: &[u8]) {
+        let (text, format) = decode_bytes(bytes, None).unwrap();
+        assert_eq!(encode_text(&text, &format).unwrap(), bytes, "{:?} read as {:?}", bytes, text);
+    }
+
+    #[test]
+    fn empty_and_newline_only_files() {
+        round_trip(b"");
+        round_trip(b"\n");
+        round_trip(b"\r\n");
+        round_trip(b"\r");
+        round_trip(b"\n\n");
+    }
+
+    #[test]
+    fn final_newline() {
+        round_trip(b"a");
+        round_trip(b"a\n");
+        round_trip(b"a\nb");
+        round_trip(b"a\nb\n");
+    }
+
+    #[test]
+    fn line_endings() {
+        round_trip(b"a\r\nb\r\n");
+        round_trip(b"a\r\nb");
+        round_trip(b"a\rb\r");
+        round_trip(b"a\rb");
+    }
+
+    #[test]
+    fn byte_order_marks() {
+        round_trip(b"\xef\xbb\xbf");
+        round_trip(b"\xef\xbb\xbf\n");
+        round_trip(b"\xef\xbb\xbfa\n");
+        round_trip(b"\xfe\xff\x00\n");
+        round_trip(b"\xff\xfea\x00\n\x00");
+    }
+
+    #[test]
+    fn latin1() {
+        round_trip(b"caf\xe9\n");
+    }
+
+    #[test]
+    fn in_place_write_keeps_existing_backups() {
+        let dir = std::env::temp_dir().join(format!("rime-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (file, link, backup) = (dir.join("file"), dir.join("link"), dir.join("file~"));
+        fs::write(&file, "old\n").unwrap();
+        fs::hard_link(&file, &link).unwrap();
+        fs::write(&backup, "mine\n").unwrap();
+        write_atomic(&file, b"new\n").unwrap();
+        assert_eq!(fs::read_to_string(&link).unwrap(), "new\n");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "mine\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+use std::cell::Cell;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::{fileio, App, CurrentEditing, LineEditor};
+
+pub const ROW: usize = 16;
+
+struct Change {
+    at: usize,
+    old: Vec<u8>,
+    len: usize,
+}
+
+pub struct HexView {
+    pub bytes: Vec<u8>,
+    pub cursor: usize,
+    pub low_nibble: bool,
+    pub insert: bool,
+    pub modified: bool,
+    undo: Vec<(Vec<Change>, usize)>,
+    pub top: Cell<usize>,
+}
+
+impl HexView {
+    pub fn new(bytes: Vec<u8>, modified: bool) -> HexView {
+        HexView {
+            bytes,
+            cursor: 0,
+            low_nibble: false,
+            insert: false,
+            modified,
+            undo: vec![],
+            top: Cell::new(0),
+        }
+    }
+
+    fn move_to(&mut self, cursor: usize) {
+        self.cursor = cursor.min(self.bytes.len().saturating_sub(1));
+        self.low_nibble = false;
+    }
+
+    fn checkpoint(&mut self) {
+        self.undo.push((vec![], self.cursor));
+    }
+
+    fn replace(&mut self, at: usize, count: usize, new: &[u8]) {
+        let old = self.bytes.splice(at..at + count, new.iter().copied()).collect();
+        if let Some((changes, _)) = self.undo.last_mut() {
+            changes.push(Change { at, old, len: new.len() });
+        }
+        self.modified = true;
+    }
+
+    fn undo(&mut self) {
+        if let Some((changes, cursor)) = self.undo.pop() {
+            for change in changes.into_iter().rev() {
+                self.bytes.splice(change.at..change.at + change.len, change.old);
+            }
+            self.move_to(cursor);
+            self.modified = true;
+        }
+    }
+
+    fn type_digit(&mut self, digit: u8) {
+        if !self.low_nibble {
+            if self.insert || self.cursor == self.bytes.len() {
+                self.replace(self.cursor, 0, &[digit << 4]);
+            } else {
+                self.replace(self.cursor, 1, &[(self.bytes[self.cursor] & 0x0f) | (digit << 4)]);
+            }
+            self.low_nibble = true;
+        } else {
+            self.replace(self.cursor, 1, &[(self.bytes[self.cursor] & 0xf0) | digit]);
+            self.cursor += 1;
+            self.low_nibble = false;
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.bytes.len() {
+            self.replace(self.cursor, 1, &[]);
+        }
+        self.move_to(self.cursor);
+    }
+
+    fn backspace(&mut self) {
+        if self.low_nibble {
+            self.low_nibble = false;
+        } else if self.cursor > 0 {
+            self.cursor -= 1;
+            if self.insert {
+                self.replace(self.cursor, 1, &[]);
+            }
+        }
+    }
+}
+
+impl App {
+    pub fn hex_key(&mut self, index: usize, key: KeyEvent) {
+        let Some(hex) = self.files[index].hex.as_mut() else { return };
+        let page = crossterm::terminal::size().map(|(_, height)| height as usize).unwrap_or(24).saturating_sub(8).max(1) * ROW;
+        match key.code {
+            KeyCode::Char('i') if key.modifiers == KeyModifiers::ALT => hex.move_to(hex.cursor.saturating_sub(ROW)),
+            KeyCode::Char('k') if key.modifiers == KeyModifiers::ALT => hex.move_to(hex.cursor + ROW),
+            KeyCode::Char('j') if key.modifiers == KeyModifiers::ALT => hex.move_to(hex.cursor.saturating_sub(1)),
+            KeyCode::Char('l') if key.modifiers == KeyModifiers::ALT => hex.move_to(hex.cursor + 1),
+            KeyCode::Up => hex.move_to(hex.cursor.saturating_sub(ROW)),
+            KeyCode::Down => hex.move_to(hex.cursor + ROW),
+            KeyCode::Left => hex.move_to(hex.cursor.saturating_sub(1)),
+            KeyCode::Right => hex.move_to(hex.cursor + 1),
+            KeyCode::PageUp => hex.move_to(hex.cursor.saturating_sub(page)),
+            KeyCode::PageDown => hex.move_to(hex.cursor + page),
+            KeyCode::Home => hex.move_to(hex.cursor - hex.cursor % ROW),
+            KeyCode::End => hex.move_to(hex.cursor - hex.cursor % ROW + ROW - 1),
+            KeyCode::Char('G') => hex.move_to(usize::MAX),
+            KeyCode::Char(c @ ('i' | 'r' | 'a')) => {
+                hex.checkpoint();
+                hex.insert = c != 'r';
+                if c == 'a' {
+                    hex.cursor = (hex.cursor + 1).min(hex.bytes.len());
+                }
+                hex.low_nibble = false;
+                self.current_editing = CurrentEditing::Page;
+            },
+            KeyCode::Char('x') => {
+                hex.checkpoint();
+                hex.delete();
+            },
+            KeyCode::Char('u') => hex.undo(),
+            KeyCode::Char(':') => self.current_editing = CurrentEditing::Command(LineEditor::new()),
+            KeyCode::Char(c) => self.current_editing = CurrentEditing::Listening(c),
+            _ => (),
+        }
+    }
+
+    pub fn hex_edit_key(&mut self, index: usize, key: KeyEvent) {
+        let Some(hex) = self.files[index].hex.as_mut() else { return };
+        match key.code {
+            KeyCode::Esc => {
+                hex.move_to(hex.cursor);
+                self.current_editing = CurrentEditing::Selecting;
+            },
+            KeyCode::Insert => hex.insert = !hex.insert,
+            KeyCode::Left => hex.move_to(hex.cursor.saturating_sub(1)),
+            KeyCode::Right => {
+                hex.cursor = (hex.cursor + 1).min(hex.bytes.len());
+                hex.low_nibble = false;
+            },
+            KeyCode::Up => hex.move_to(hex.cursor.saturating_sub(ROW)),
+            KeyCode::Down => hex.move_to(hex.cursor + ROW),
+            KeyCode::Backspace => hex.backspace(),
+            KeyCode::Delete => hex.delete(),
+            KeyCode::Char(c) => match c.to_digit(16) {
+                Some(digit) => hex.type_digit(digit as u8),
+                None => self.error(format!("E474: Not a hex digit: {}", c)),
+            },
+            _ => (),
+        }
+    }
+
+    pub fn toggle_hex(&mut self, index: usize) {
+        let file = &mut self.files[index];
+        match file.hex.take() {
+            Some(hex) => match fileio::decode_bytes(&hex.bytes, None) {
+                Ok((text, format)) => {
+                    file.set_format(format);
+                    file.follow_signs(|undo_tree| undo_tree.replace_text(text.clone(), 0));
+                    if !hex.modified {
+                        file.set_saved(text);
+                    }
+                },
+                Err(error) => {
+                    file.hex = Some(hex);
+                    self.error(format!("E474: {}", error));
+                },
+            },
+            None => {
+                let format = file.format(&self.options);
+                match fileio::encode_text(&file.text(), &format) {
+                    Ok(bytes) => file.hex = Some(HexView::new(bytes, file.is_modified())),
+                    Err(error) => self.error(error.to_string()),
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_reverts_each_edit_session() {
+        let mut hex = HexView::new(vec![0x00, 0x11, 0x22], false);
+        hex.checkpoint();
+        hex.move_to(1);
+        hex.type_digit(0xa);
+        hex.type_digit(0xb);
+        hex.insert = true;
+        hex.type_digit(0xc);
+        hex.type_digit(0xd);
+        hex.backspace();
+        hex.type_digit(0xe);
+        hex.type_digit(0xf);
+        assert_eq!(hex.bytes, [0x00, 0xab, 0xef, 0x22]);
+        hex.checkpoint();
+        hex.move_to(0);
+        hex.delete();
+        assert_eq!(hex.bytes, [0xab, 0xef, 0x22]);
+        hex.undo();
+        assert_eq!((hex.bytes.as_slice(), hex.cursor), (&[0x00, 0xab, 0xef, 0x22][..], 3));
+        hex.undo();
+        assert_eq!((hex.bytes.as_slice(), hex.cursor), (&[0x00, 0x11, 0x22][..], 0));
+        assert!(hex.undo.is_empty());
+    }
+}
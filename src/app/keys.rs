@@ -0,0 +1,135 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+use crate::{app::{undotree::UndoTree, viewport::Scroll, window::CTRL_W, App, CurrentEditing, CurrentScreenMode}, Cursor, LineEditor};
+
+impl App {
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        self.sync_windows();
+        match self.current_screen {
+            CurrentScreenMode::Main => match key.code {
+                KeyCode::Char('q') => return true,
+                _ => (),
+            },
+            CurrentScreenMode::Config => {
+
+            },
+            CurrentScreenMode::File(index) => {
+                match &self.current_editing {
+                    CurrentEditing::Selecting if key.code == KeyCode::Char('w') && key.modifiers == KeyModifiers::CONTROL => {
+                        self.current_editing = CurrentEditing::Listening(CTRL_W);
+                    },
+                    CurrentEditing::Page if self.files[index].hex.is_some() => self.hex_edit_key(index, key),
+                    CurrentEditing::Page if self.files[index].large.is_some() => self.large_edit_key(index, key),
+                    CurrentEditing::Page if key.kind == KeyEventKind::Press => 
+                        match key.code {
+                            KeyCode::Char('i') if key.modifiers == KeyModifiers::ALT => self.files[index].undo_tree.move_cursor_up(),
+                            KeyCode::Char('k') if key.modifiers == KeyModifiers::ALT => self.files[index].undo_tree.move_cursor_down(),
+                            KeyCode::Char('j') if key.modifiers == KeyModifiers::ALT => self.files[index].undo_tree.move_cursor_left(),
+                            KeyCode::Char('l') if key.modifiers == KeyModifiers::ALT => self.files[index].undo_tree.move_cursor_right(),
+                            KeyCode::Esc => self.current_editing = CurrentEditing::Selecting,
+                            KeyCode::Insert => self.current_editing = CurrentEditing::Replace,
+                            KeyCode::Backspace => self.files[index].del_char(),
+                            KeyCode::Enter => self.files[index].new_line(),
+                            KeyCode::Tab => self.insert_tab(index),
+                            KeyCode::Char(c) => self.files[index].undo_tree.add_char(c),
+                            _ => (),
+                        },
+                    CurrentEditing::Replace if key.kind == KeyEventKind::Press =>
+                        match key.code {
+                            KeyCode::Char('i') if key.modifiers == KeyModifiers::ALT => self.files[index].undo_tree.move_cursor_up(),
+                            KeyCode::Char('k') if key.modifiers == KeyModifiers::ALT => self.files[index].undo_tree.move_cursor_down(),
+                            KeyCode::Char('j') if key.modifiers == KeyModifiers::ALT => self.files[index].undo_tree.move_cursor_left(),
+                            KeyCode::Char('l') if key.modifiers == KeyModifiers::ALT => self.files[index].undo_tree.move_cursor_right(),
+                            KeyCode::Esc => self.current_editing = CurrentEditing::Selecting,
+                            KeyCode::Insert => self.current_editing = CurrentEditing::Page,
+                            KeyCode::Backspace => self.files[index].undo_tree.move_cursor_left(),
+                            KeyCode::Enter => self.files[index].new_line(),
+                            KeyCode::Char(c) => self.files[index].undo_tree.replace_char(c),
+                            _ => (),
+                        },
+                    CurrentEditing::Command(_) | CurrentEditing::Search(_) => self.line_key(key),
+                    CurrentEditing::Prompt(_) => self.prompt_key(key),
+                    CurrentEditing::Selecting if self.files[index].large.is_some() => self.large_key(index, key),
+                    CurrentEditing::Selecting if self.files[index].hex.is_some() => self.hex_key(index, key),
+                    CurrentEditing::Selecting if self.files[index].explorer.is_some() => self.explorer_key(index, key),
+                    CurrentEditing::Selecting => match key.code {
+                        KeyCode::Char('i') if key.modifiers == KeyModifiers::ALT => self.files[index].undo_tree.move_cursor_up(),
+                        KeyCode::Char('k') if key.modifiers == KeyModifiers::ALT => self.files[index].undo_tree.move_cursor_down(),
+                        KeyCode::Char('j') if key.modifiers == KeyModifiers::ALT => self.files[index].undo_tree.move_cursor_left(),
+                        KeyCode::Char('l') if key.modifiers == KeyModifiers::ALT => self.files[index].undo_tree.move_cursor_right(),
+                        KeyCode::Char('e') if key.modifiers == KeyModifiers::CONTROL => self.scroll(index, Scroll::LineDown),
+                        KeyCode::Char('y') if key.modifiers == KeyModifiers::CONTROL => self.scroll(index, Scroll::LineUp),
+                        KeyCode::Char('d') if key.modifiers == KeyModifiers::CONTROL => self.scroll(index, Scroll::HalfDown),
+                        KeyCode::Char('u') if key.modifiers == KeyModifiers::CONTROL => self.scroll(index, Scroll::HalfUp),
+                        KeyCode::Char('f') if key.modifiers == KeyModifiers::CONTROL => self.scroll(index, Scroll::PageDown),
+                        KeyCode::Char('b') if key.modifiers == KeyModifiers::CONTROL => self.scroll(index, Scroll::PageUp),
+                        KeyCode::PageDown => self.scroll(index, Scroll::PageDown),
+                        KeyCode::PageUp => self.scroll(index, Scroll::PageUp),
+                        KeyCode::Char('i') => {
+                            let (text, cursor) = if let Some(node) = &self.files[index].undo_tree.current {
+                                (node.borrow().text.clone(), node.borrow().cursor.clone())
+                            } else {("".to_owned(), Cursor::new(0, 1))};
+                            self.files[index].undo_tree.add_node(text, cursor);
+                            self.current_editing = CurrentEditing::Page;
+                        }
+                        KeyCode::Char('R') => self.files[index].follow_signs(UndoTree::redo),
+                        KeyCode::Char('u') => self.files[index].follow_signs(UndoTree::undo),
+                        KeyCode::Char(':') => self.current_editing = CurrentEditing::Command(LineEditor::new()),
+                        KeyCode::Char('/') => self.current_editing = CurrentEditing::Search(LineEditor::new()),
+                        KeyCode::Char('n') => self.search(String::new(), true),
+                        KeyCode::Char('N') => self.search(String::new(), false),
+                        _ => if let KeyCode::Char(c) = key.code {
+                            self.current_editing = CurrentEditing::Listening(c)
+                        },
+                    }
+                    CurrentEditing::Listening(c) => match c {
+                        &CTRL_W => self.window_key(key),
+                        'g' => match key.code {
+                            KeyCode::Char('t') => {
+                                self.current_screen = if self.files.len() > index + 1 {
+                                    CurrentScreenMode::File(index + 1)
+                                } else {
+                                    CurrentScreenMode::File(0)
+                                };
+                                self.current_editing = CurrentEditing::Selecting;
+                            },
+                            KeyCode::Char('T') => {
+                                self.current_screen = if index as i32 - 1 > - 1 {
+                                    CurrentScreenMode::File(index - 1)
+                                } else {
+                                    CurrentScreenMode::File(self.files.len() - 1)
+                                };
+                                self.current_editing = CurrentEditing::Selecting;
+                            },
+                            KeyCode::Char('j') => {
+                                self.display_line(index, true);
+                                self.current_editing = CurrentEditing::Selecting;
+                            },
+                            KeyCode::Char('k') => {
+                                self.display_line(index, false);
+                                self.current_editing = CurrentEditing::Selecting;
+                            },
+                            _ => self.current_editing = CurrentEditing::Selecting,
+                        },
+                        'z' => {
+                            match key.code {
+                                KeyCode::Char('z') => self.scroll(index, Scroll::Center),
+                                KeyCode::Char('t') => self.scroll(index, Scroll::Top),
+                                KeyCode::Char('b') => self.scroll(index, Scroll::Bottom),
+                                KeyCode::Char('h') => self.scroll(index, Scroll::ColumnLeft),
+                                KeyCode::Char('l') => self.scroll(index, Scroll::ColumnRight),
+                                KeyCode::Char('H') => self.scroll(index, Scroll::HalfLeft),
+                                KeyCode::Char('L') => self.scroll(index, Scroll::HalfRight),
+                                _ => (),
+                            }
+                            self.current_editing = CurrentEditing::Selecting;
+                        },
+                        _ => self.current_editing = CurrentEditing::Selecting,
+                    }
+                    _ => (),
+                }
+            }
+        }
+        false
+    }
+}
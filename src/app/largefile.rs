@@ -0,0 +1,778 @@
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    fs,
+    io::{self, Write},
+    os::unix::fs::{FileExt, MetadataExt},
+    path::Path,
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::app::{
+    excommand::{parse_range, split_pattern, split_until, substitute, LINE_COMMANDS},
+    fileio, App, CurrentEditing, LineEditor, Message,
+};
+
+const INDEX_CHUNK: usize = 1 << 24;
+const MAX_LINE: usize = 4096;
+const UNDO_LIMIT: usize = 1 << 24;
+
+#[derive(Clone)]
+struct Edit {
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
+enum Source<'a> {
+    Edited(&'a str),
+    Original(usize),
+}
+
+enum Segment<'a> {
+    Original { display: usize, start: usize, end: usize },
+    Edited { display: usize, lines: &'a [String] },
+}
+
+impl Segment<'_> {
+    fn display(&self) -> (usize, usize) {
+        match self {
+            Segment::Original { display, start, end } => (*display, display + end - start),
+            Segment::Edited { display, lines } => (*display, display + lines.len()),
+        }
+    }
+}
+
+pub struct LargeFile {
+    handle: fs::File,
+    len: usize,
+    line_starts: Vec<usize>,
+    indexed: usize,
+    newline: &'static str,
+    view_only: Option<&'static str>,
+    edits: Vec<Edit>,
+    undo: Vec<(Vec<Edit>, usize)>,
+    pub modified: bool,
+    pub line: usize,
+    pub column: usize,
+    pub top: Cell<usize>,
+    pub left: Cell<usize>,
+}
+
+impl LargeFile {
+    pub fn open(handle: fs::File) -> io::Result<LargeFile> {
+        let len = handle.metadata()?.len() as usize;
+        let mut large = LargeFile {
+            handle,
+            len,
+            line_starts: vec![0],
+            indexed: 0,
+            newline: "\n",
+            view_only: None,
+            edits: vec![],
+            undo: vec![],
+            modified: false,
+            line: 0,
+            column: 0,
+            top: Cell::new(0),
+            left: Cell::new(0),
+        };
+        let head = large.read(0, len.min(MAX_LINE * 16));
+        match head.iter().position(|&b| b == b'\n') {
+            Some(end) if end > 0 && head[end - 1] == b'\r' => large.newline = "\r\n",
+            None if head.contains(&b'\r') => large.view_only = Some("CR line endings"),
+            _ => (),
+        }
+        if head.starts_with(&[0xfe, 0xff]) || head.starts_with(&[0xff, 0xfe]) || head.contains(&0) {
+            large.view_only = Some("UTF-16 or binary file");
+        }
+        Ok(large)
+    }
+
+    fn read(&self, start: usize, end: usize) -> Vec<u8> {
+        let mut buffer = vec![0; end.saturating_sub(start)];
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match self.handle.read_at(&mut buffer[filled..], (start + filled) as u64) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+                Err(_) => break,
+            }
+        }
+        buffer.truncate(filled);
+        buffer
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        self.indexed == self.len
+    }
+
+    pub fn progress(&self) -> usize {
+        (self.indexed as u128 * 100 / self.len.max(1) as u128) as usize
+    }
+
+    pub fn index_step(&mut self) {
+        let start = self.indexed;
+        let end = (start + INDEX_CHUNK).min(self.len);
+        let bytes = self.read(start, end);
+        self.line_starts.extend(bytes.iter().enumerate().filter(|(_, b)| **b == b'\n').map(|(i, _)| start + i + 1));
+        self.indexed = start + bytes.len();
+        if self.indexed < end {
+            self.len = self.indexed;
+        }
+        if self.is_indexed() && self.len > 0 && self.line_starts.last() == Some(&self.len) {
+            self.line_starts.pop();
+        }
+    }
+
+    pub fn index_all(&mut self) {
+        while !self.is_indexed() {
+            self.index_step();
+        }
+    }
+
+    fn original_lines(&self) -> usize {
+        if self.is_indexed() {
+            self.line_starts.len()
+        } else {
+            self.line_starts.len() - 1
+        }
+    }
+
+    fn original_end(&self, line: usize) -> usize {
+        self.line_starts.get(line).copied().unwrap_or(self.len)
+    }
+
+    fn original_bytes(&self, line: usize, limit: usize) -> Vec<u8> {
+        let start = self.line_starts[line];
+        let end = self.original_end(line + 1).min(start.saturating_add(limit));
+        let mut bytes = self.read(start, end);
+        if bytes.ends_with(b"\n") {
+            bytes.pop();
+            if bytes.ends_with(b"\r") {
+                bytes.pop();
+            }
+        }
+        bytes
+    }
+
+    fn original_line(&self, line: usize, limit: usize) -> String {
+        String::from_utf8_lossy(&self.original_bytes(line, limit)).into_owned()
+    }
+
+    fn segments(&self) -> Vec<Segment<'_>> {
+        let mut segments = vec![];
+        let (mut original, mut display) = (0, 0);
+        for edit in &self.edits {
+            if edit.start > original {
+                segments.push(Segment::Original { display, start: original, end: edit.start });
+                display += edit.start - original;
+            }
+            segments.push(Segment::Edited { display, lines: &edit.lines });
+            display += edit.lines.len();
+            original = edit.end;
+        }
+        let end = self.original_lines();
+        if end > original {
+            segments.push(Segment::Original { display, start: original, end });
+        }
+        segments
+    }
+
+    pub fn line_count(&self) -> usize {
+        let shift: isize = self.edits.iter().map(|edit| edit.lines.len() as isize - (edit.end - edit.start) as isize).sum();
+        (self.original_lines() as isize + shift).max(0) as usize
+    }
+
+    fn ensure_line(&mut self, line: usize) {
+        while !self.is_indexed() && self.line_count() <= line {
+            self.index_step();
+        }
+    }
+
+    fn source(&self, line: usize) -> Option<Source<'_>> {
+        let mut shift = 0;
+        for edit in &self.edits {
+            let start = (edit.start as isize + shift) as usize;
+            if line < start {
+                break;
+            }
+            if line < start + edit.lines.len() {
+                return Some(Source::Edited(&edit.lines[line - start]));
+            }
+            shift += edit.lines.len() as isize - (edit.end - edit.start) as isize;
+        }
+        let original = (line as isize - shift) as usize;
+        (original < self.original_lines()).then_some(Source::Original(original))
+    }
+
+    fn text(&self, line: usize, limit: usize) -> Option<Cow<'_, str>> {
+        match self.source(line)? {
+            Source::Edited(text) => Some(Cow::Borrowed(text)),
+            Source::Original(original) => Some(Cow::Owned(self.original_line(original, limit))),
+        }
+    }
+
+    pub fn line(&self, line: usize) -> Option<Cow<'_, str>> {
+        self.text(line, MAX_LINE)
+    }
+
+    fn full_line(&self, line: usize) -> Result<String, String> {
+        match self.source(line) {
+            Some(Source::Edited(text)) => Ok(text.to_owned()),
+            Some(Source::Original(original)) => String::from_utf8(self.original_bytes(original, usize::MAX))
+                .map_err(|_| format!("Line {} is not valid UTF-8: large files can only be edited in UTF-8", line + 1)),
+            None => Ok(String::new()),
+        }
+    }
+
+    pub fn editable(&self) -> Result<(), String> {
+        match self.view_only {
+            Some(reason) => Err(format!("{}: large files can only be edited in UTF-8 with LF or CRLF line endings", reason)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn cursor_column(&self) -> usize {
+        let line = self.line(self.line).unwrap_or_default();
+        line[..self.column.min(line.len())].chars().count()
+    }
+
+    pub fn go_to(&mut self, line: usize) {
+        self.ensure_line(line);
+        self.line = line.min(self.line_count().saturating_sub(1));
+        self.move_to(self.column);
+    }
+
+    fn move_to(&mut self, column: usize) {
+        let line = self.line(self.line).unwrap_or_default();
+        let mut column = column.min(line.len());
+        while !line.is_char_boundary(column) {
+            column -= 1;
+        }
+        self.column = column;
+    }
+
+    fn move_left(&mut self) {
+        let line = self.line(self.line).unwrap_or_default();
+        let column = line[..self.column.min(line.len())].chars().next_back().map_or(0, |c| self.column - c.len_utf8());
+        self.move_to(column);
+    }
+
+    fn move_right(&mut self) {
+        let line = self.line(self.line).unwrap_or_default();
+        let column = line[self.column.min(line.len())..].chars().next().map_or(self.column, |c| self.column + c.len_utf8());
+        self.move_to(column);
+    }
+
+    fn splice(&mut self, at: usize, count: usize, new: Vec<String>) {
+        let end = at + count;
+        let delta = |edit: &Edit| edit.lines.len() as isize - (edit.end - edit.start) as isize;
+        let (mut first, mut shift) = (0, 0);
+        while let Some(edit) = self.edits.get(first) && (edit.start as isize + shift) as usize + edit.lines.len() < at {
+            shift += delta(edit);
+            first += 1;
+        }
+        let (mut from, mut to, mut last, mut shift_after) = (at, end, first, shift);
+        while let Some(edit) = self.edits.get(last) && (edit.start as isize + shift_after) as usize <= to {
+            let start = (edit.start as isize + shift_after) as usize;
+            from = from.min(start);
+            to = to.max(start + edit.lines.len());
+            shift_after += delta(edit);
+            last += 1;
+        }
+        let edited = |line| self.text(line, usize::MAX).map(Cow::into_owned).unwrap_or_default();
+        let mut lines: Vec<String> = (from..at).map(edited).collect();
+        lines.extend(new);
+        lines.extend((end..to).map(edited));
+        let edit = Edit { start: (from as isize - shift) as usize, end: (to as isize - shift_after) as usize, lines };
+        self.edits.splice(first..last, [edit]);
+        self.edits.retain(|edit| edit.start != edit.end || !edit.lines.is_empty());
+        self.modified = true;
+    }
+
+    fn set_line(&mut self, line: usize, text: String) {
+        let count = usize::from(line < self.line_count());
+        self.splice(line, count, vec![text]);
+    }
+
+    fn checkpoint(&mut self) {
+        let size: usize = self.edits.iter().flat_map(|edit| &edit.lines).map(|line| line.len()).sum();
+        if size > UNDO_LIMIT {
+            self.undo.clear();
+            return;
+        }
+        self.undo.push((self.edits.clone(), self.line));
+    }
+
+    fn undo(&mut self) -> bool {
+        let Some((edits, line)) = self.undo.pop() else { return false };
+        self.edits = edits;
+        self.modified = true;
+        self.go_to(line);
+        true
+    }
+
+    pub fn swap_text(&self) -> String {
+        let mut text = String::new();
+        for edit in &self.edits {
+            text += &format!("{} {} {}\n", edit.start, edit.end, edit.lines.len());
+            for line in &edit.lines {
+                text += line;
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    pub fn recover(&mut self, text: &str) -> bool {
+        self.index_all();
+        let mut lines = text.split('\n');
+        let mut edits: Vec<Edit> = vec![];
+        while let Some(header) = lines.next() && !header.is_empty() {
+            let numbers: Vec<usize> = header.split(' ').filter_map(|n| n.parse().ok()).collect();
+            let &[start, end, count] = numbers.as_slice() else { return false };
+            if start > end || end > self.original_lines() || edits.last().is_some_and(|last| last.end > start) {
+                return false;
+            }
+            let Some(lines) = (0..count).map(|_| lines.next().map(str::to_owned)).collect() else { return false };
+            edits.push(Edit { start, end, lines });
+        }
+        self.checkpoint();
+        self.edits = edits;
+        self.modified = true;
+        self.go_to(self.line);
+        true
+    }
+
+    pub fn insert(&mut self, text: &str) -> Result<(), String> {
+        let mut line = self.full_line(self.line)?;
+        let column = self.column.min(line.len());
+        line.insert_str(column, text);
+        self.set_line(self.line, line);
+        self.column = column + text.len();
+        Ok(())
+    }
+
+    fn split_line(&mut self) -> Result<(), String> {
+        let mut line = self.full_line(self.line)?;
+        let after = line.split_off(self.column.min(line.len()));
+        let count = usize::from(self.line < self.line_count());
+        self.splice(self.line, count, vec![line, after]);
+        self.line += 1;
+        self.column = 0;
+        Ok(())
+    }
+
+    fn backspace(&mut self) -> Result<(), String> {
+        if self.column > 0 {
+            let mut line = self.full_line(self.line)?;
+            self.move_left();
+            line.remove(self.column);
+            self.set_line(self.line, line);
+        } else if self.line > 0 && self.line < self.line_count() {
+            let previous = self.full_line(self.line - 1)?;
+            let joined = previous.clone() + &self.full_line(self.line)?;
+            self.splice(self.line - 1, 2, vec![joined]);
+            self.line -= 1;
+            self.column = previous.len();
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self) -> Result<(), String> {
+        let mut line = self.full_line(self.line)?;
+        if self.column < line.len() {
+            line.remove(self.column);
+            self.set_line(self.line, line);
+        } else if self.line + 1 < self.line_count() {
+            line.push_str(&self.full_line(self.line + 1)?);
+            self.splice(self.line, 2, vec![line]);
+        }
+        Ok(())
+    }
+
+    fn ends_with_newline(&self) -> bool {
+        self.len == 0 || self.read(self.len - 1, self.len) == b"\n"
+    }
+
+    fn copy(&self, out: &mut dyn Write, start: usize, end: usize) -> io::Result<usize> {
+        let mut copied = 0;
+        for from in (start..end).step_by(INDEX_CHUNK) {
+            let bytes = self.read(from, (from + INDEX_CHUNK).min(end));
+            out.write_all(&bytes)?;
+            copied += bytes.len();
+        }
+        Ok(copied)
+    }
+
+    fn write(&self, out: &mut dyn Write) -> io::Result<usize> {
+        let total = self.line_count();
+        let eol = self.ends_with_newline();
+        let mut written = 0;
+        for segment in self.segments() {
+            let (_, display_end) = segment.display();
+            let more = display_end < total;
+            match segment {
+                Segment::Original { start, end, .. } => {
+                    written += self.copy(out, self.line_starts[start], self.original_end(end))?;
+                    if end == self.original_lines() && !eol && more {
+                        out.write_all(self.newline.as_bytes())?;
+                        written += self.newline.len();
+                    }
+                },
+                Segment::Edited { display, lines } => for (i, line) in lines.iter().enumerate() {
+                    out.write_all(line.as_bytes())?;
+                    written += line.len();
+                    if display + i + 1 < total || eol {
+                        out.write_all(self.newline.as_bytes())?;
+                        written += self.newline.len();
+                    }
+                },
+            }
+        }
+        Ok(written)
+    }
+
+    pub fn save(&mut self, path: &Path) -> io::Result<(usize, usize)> {
+        self.index_all();
+        let own = self.handle.metadata()?;
+        let same = fs::metadata(path).is_ok_and(|metadata| (metadata.dev(), metadata.ino()) == (own.dev(), own.ino()));
+        let mut written = 0;
+        fileio::write_atomic_with(path, !same, &mut |file| {
+            written = self.write(file)?;
+            Ok(())
+        })?;
+        Ok((self.line_count(), written))
+    }
+
+    fn find_bytes(&self, start: usize, end: usize, needle: &[u8], forward: bool) -> Option<usize> {
+        let overlap = needle.len() - 1;
+        if forward {
+            let mut from = start;
+            while from < end {
+                let to = (from + INDEX_CHUNK + overlap).min(end);
+                let bytes = self.read(from, to);
+                if let Some(i) = bytes.windows(needle.len()).position(|window| window == needle) {
+                    return Some(from + i);
+                }
+                if to == end || bytes.len() < to - from {
+                    return None;
+                }
+                from = to - overlap;
+            }
+        } else {
+            let mut to = end;
+            while to > start {
+                let from = to.saturating_sub(INDEX_CHUNK + overlap).max(start);
+                let bytes = self.read(from, to);
+                if let Some(i) = bytes.windows(needle.len()).rposition(|window| window == needle) {
+                    return Some(from + i);
+                }
+                if from == start {
+                    return None;
+                }
+                to = from + overlap;
+            }
+        }
+        None
+    }
+
+    fn find_in(&self, lines: (usize, usize), pattern: &str, forward: bool) -> Option<usize> {
+        let mut segments = self.segments();
+        if !forward {
+            segments.reverse();
+        }
+        for segment in segments {
+            let (display, display_end) = segment.display();
+            let (from, to) = (lines.0.max(display), lines.1.min(display_end));
+            if from >= to {
+                continue;
+            }
+            match segment {
+                Segment::Original { start, .. } => {
+                    let (first, last) = (from - display + start, to - display + start);
+                    let found = self.find_bytes(self.line_starts[first], self.original_end(last), pattern.as_bytes(), forward);
+                    if let Some(offset) = found {
+                        let original = self.line_starts.partition_point(|&start| start <= offset) - 1;
+                        return Some(original - start + display);
+                    }
+                },
+                Segment::Edited { lines, .. } => {
+                    let mut found = (from..to).filter(|line| lines[line - display].contains(pattern));
+                    let found = if forward { found.next() } else { found.next_back() };
+                    if found.is_some() {
+                        return found;
+                    }
+                },
+            }
+        }
+        None
+    }
+
+    pub fn search(&mut self, pattern: &str, forward: bool) -> bool {
+        self.index_all();
+        let count = self.line_count();
+        let ranges = if forward {
+            [(self.line + 1, count), (0, self.line + 1)]
+        } else {
+            [(0, self.line), (self.line, count)]
+        };
+        for lines in ranges {
+            if let Some(line) = self.find_in(lines, pattern, forward) {
+                self.line = line;
+                self.column = 0;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl App {
+    // Indexes one chunk of the first large file still being indexed. This runs on the UI thread:
+    // the main loop calls it between events, so a key press waits for at most one chunk.
+    pub fn index_large_files(&mut self) -> bool {
+        let pending = self.files.iter().position(|file| file.large.as_ref().is_some_and(|large| !large.is_indexed()));
+        let index = match pending {
+            Some(index) => index,
+            None => return false,
+        };
+        let name = self.files[index].name.clone();
+        let Some(large) = self.files[index].large.as_mut() else { return false };
+        large.index_step();
+        if large.is_indexed() {
+            let message = format!("\"{}\" {}L, {}B (large file: no syntax highlighting, line undo only)", name, large.line_count(), large.len());
+            self.info(message);
+        } else {
+            let text = format!("\"{}\" indexing lines... {}%", name, large.progress());
+            self.message = Some(Message { text, error: false });
+        }
+        true
+    }
+
+    pub fn large_key(&mut self, index: usize, key: KeyEvent) {
+        let Some(large) = self.files[index].large.as_mut() else { return };
+        let page = crossterm::terminal::size().map(|(_, height)| height as usize).unwrap_or(24).saturating_sub(8).max(1);
+        match key.code {
+            KeyCode::Char('i') if key.modifiers == KeyModifiers::ALT => large.go_to(large.line.saturating_sub(1)),
+            KeyCode::Char('k') if key.modifiers == KeyModifiers::ALT => large.go_to(large.line + 1),
+            KeyCode::Char('j') if key.modifiers == KeyModifiers::ALT => large.move_left(),
+            KeyCode::Char('l') if key.modifiers == KeyModifiers::ALT => large.move_right(),
+            KeyCode::Up => large.go_to(large.line.saturating_sub(1)),
+            KeyCode::Down => large.go_to(large.line + 1),
+            KeyCode::Left => large.move_left(),
+            KeyCode::Right => large.move_right(),
+            KeyCode::PageUp => large.go_to(large.line.saturating_sub(page)),
+            KeyCode::PageDown => large.go_to(large.line + page),
+            KeyCode::Home => large.go_to(0),
+            KeyCode::End | KeyCode::Char('G') => {
+                large.index_all();
+                large.go_to(usize::MAX);
+            },
+            KeyCode::Char('i') => match large.editable() {
+                Ok(_) => {
+                    large.checkpoint();
+                    self.current_editing = CurrentEditing::Page;
+                },
+                Err(error) => self.error(error),
+            },
+            KeyCode::Char('u') => if !large.undo() {
+                self.info("Already at oldest change".to_owned());
+            },
+            KeyCode::Char(':') => self.current_editing = CurrentEditing::Command(LineEditor::new()),
+            KeyCode::Char('/') => self.current_editing = CurrentEditing::Search(LineEditor::new()),
+            KeyCode::Char('n') => self.search(String::new(), true),
+            KeyCode::Char('N') => self.search(String::new(), false),
+            KeyCode::Char(c) => self.current_editing = CurrentEditing::Listening(c),
+            _ => (),
+        }
+    }
+
+    pub fn large_edit_key(&mut self, index: usize, key: KeyEvent) {
+        let Some(large) = self.files[index].large.as_mut() else { return };
+        let mut result = Ok(());
+        match key.code {
+            KeyCode::Esc => self.current_editing = CurrentEditing::Selecting,
+            KeyCode::Char('i') if key.modifiers == KeyModifiers::ALT => large.go_to(large.line.saturating_sub(1)),
+            KeyCode::Char('k') if key.modifiers == KeyModifiers::ALT => large.go_to(large.line + 1),
+            KeyCode::Char('j') if key.modifiers == KeyModifiers::ALT => large.move_left(),
+            KeyCode::Char('l') if key.modifiers == KeyModifiers::ALT => large.move_right(),
+            KeyCode::Up => large.go_to(large.line.saturating_sub(1)),
+            KeyCode::Down => large.go_to(large.line + 1),
+            KeyCode::Left => large.move_left(),
+            KeyCode::Right => large.move_right(),
+            KeyCode::Backspace => result = large.backspace(),
+            KeyCode::Delete => result = large.delete(),
+            KeyCode::Enter => result = large.split_line(),
+            KeyCode::Tab => self.insert_tab(index),
+            KeyCode::Char(c) => result = large.insert(c.encode_utf8(&mut [0; 4])),
+            _ => (),
+        }
+        if let Err(error) = result {
+            self.error(error);
+        }
+    }
+
+    pub fn large_line_command(&mut self, index: usize, command: &str) -> Option<Result<(), String>> {
+        let large = self.files[index].large.as_mut()?;
+        if command.trim_start().starts_with(|c: char| c.is_ascii_digit() || "%.$,+-".contains(c)) {
+            large.index_all();
+        }
+        let (range, rest) = match parse_range(command, large.line, large.line_count()) {
+            Ok(parsed) => parsed,
+            Err(error) => return Some(Err(error)),
+        };
+        let rest = rest.trim_start();
+        let (name, args) = rest.split_at(rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len()));
+        let (start, end) = match (range, name) {
+            (Some((0, _)), _) => return Some(Err("E16: Invalid range".to_owned())),
+            (Some((_, end)), "") => {
+                large.go_to(end - 1);
+                return Some(Ok(()));
+            },
+            (Some((start, end)), _) => (start - 1, end - 1),
+            (None, _) => (large.line, large.line),
+        };
+        match name {
+            "d" | "de" | "del" | "delete" => {
+                if let Err(error) = large.editable() {
+                    return Some(Err(error));
+                }
+                large.checkpoint();
+                large.splice(start, end + 1 - start, vec![]);
+                large.go_to(start);
+                Some(Ok(()))
+            },
+            "s" | "substitute" => Some(self.large_substitute(index, start, end, args)),
+            "p" => {
+                let lines: Vec<String> = (start..=end)
+                    .map_while(|line| large.line(line).map(|text| format!("{:>4} {}", line + 1, text)))
+                    .collect();
+                for line in lines {
+                    self.info(line);
+                }
+                self.show_messages = true;
+                Some(Ok(()))
+            },
+            name if LINE_COMMANDS.contains(&name) => Some(Err("E319: Sorry, the command is not available for large files".to_owned())),
+            _ if range.is_some() => Some(Err("E481: No range allowed".to_owned())),
+            _ => None,
+        }
+    }
+
+    fn large_substitute(&mut self, index: usize, start: usize, end: usize, args: &str) -> Result<(), String> {
+        let (delimiter, pattern, rest) = split_pattern(args)?;
+        let pattern = self.pattern_or_last(pattern)?;
+        let (replacement, flags) = split_until(rest, delimiter);
+        let ignore_case = !flags.contains('I') && (flags.contains('i') || self.ignore_case(index, &pattern));
+        let Some(large) = self.files[index].large.as_mut() else { return Ok(()) };
+        large.editable()?;
+        let mut changed: Vec<(usize, String)> = vec![];
+        for line in start..=end.min(large.line_count().saturating_sub(1)) {
+            if let Some(new) = substitute(&large.full_line(line)?, &pattern, &replacement, flags.contains('g'), ignore_case) {
+                changed.push((line, new));
+            }
+        }
+        let (Some(&(first, _)), Some(&(last, _))) = (changed.first(), changed.last()) else {
+            return Err(format!("E486: Pattern not found: {}", pattern));
+        };
+        let mut changed = changed.into_iter().peekable();
+        let lines: Vec<String> = (first..=last)
+            .map(|line| match changed.next_if(|(changed, _)| *changed == line) {
+                Some((_, new)) => Ok(new),
+                None => large.full_line(line),
+            })
+            .collect::<Result<_, _>>()?;
+        large.checkpoint();
+        large.splice(first, last + 1 - first, lines);
+        large.go_to(last);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(name: &str, bytes: &[u8]) -> LargeFile {
+        let path = std::env::temp_dir().join(format!("rime-large-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let mut large = LargeFile::open(fs::File::open(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        large.index_all();
+        large
+    }
+
+    fn written(large: &LargeFile) -> Vec<u8> {
+        let mut out = vec![];
+        large.write(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn swap_text_recovers_the_edits() {
+        let mut large = open("swap", b"a\nb\nc\nd\n");
+        large.set_line(1, "B".to_owned());
+        large.splice(3, 1, vec![String::new(), "e".to_owned()]);
+        let mut recovered = open("recovered", b"a\nb\nc\nd\n");
+        assert!(recovered.recover(&large.swap_text()));
+        assert_eq!(written(&recovered), b"a\nB\nc\n\ne\n");
+        assert!(!recovered.recover("0 9 1\nx\n"));
+    }
+
+    #[test]
+    fn unedited_files_are_written_unchanged() {
+        for bytes in [&b""[..], b"\n", b"a", b"a\nb\n", b"a\nb", b"a\r\nb\r\n", b"caf\xe9\n"] {
+            assert_eq!(written(&open("unedited", bytes)), bytes);
+        }
+    }
+
+    #[test]
+    fn splice_merges_edits_into_segments() {
+        let mut large = open("segments", b"a\nb\nc\nd\ne\n");
+        large.splice(1, 1, vec!["B".to_owned(), "B2".to_owned()]);
+        large.splice(4, 1, vec![]);
+        let ranges: Vec<(usize, usize)> = large.segments().iter().map(Segment::display).collect();
+        assert_eq!(ranges, [(0, 1), (1, 3), (3, 4), (4, 4), (4, 5)]);
+        assert_eq!(large.line_count(), 5);
+        assert_eq!(written(&large), b"a\nB\nB2\nc\ne\n");
+        large.splice(2, 2, vec!["x".to_owned()]);
+        assert_eq!(large.edits.len(), 1);
+        assert_eq!(written(&large), b"a\nB\nx\ne\n");
+    }
+
+    #[test]
+    fn edits_keep_line_endings() {
+        let mut large = open("crlf", b"a\r\nb\r\n");
+        large.go_to(1);
+        large.insert("c").unwrap();
+        large.split_line().unwrap();
+        assert_eq!(written(&large), b"a\r\nc\r\nb\r\n");
+        let mut large = open("noeol", b"a\nb");
+        large.splice(2, 0, vec!["c".to_owned()]);
+        assert_eq!(written(&large), b"a\nb\nc");
+        large.go_to(1);
+        large.backspace().unwrap();
+        assert_eq!(written(&large), b"ab\nc");
+    }
+
+    #[test]
+    fn lines_that_are_not_utf8_are_not_edited() {
+        let mut large = open("latin1", b"caf\xe9\nb\n");
+        assert!(large.insert("x").is_err());
+        large.go_to(1);
+        large.insert("x").unwrap();
+        large.column = 0;
+        assert!(large.backspace().is_err());
+        assert_eq!(written(&large), b"caf\xe9\nxb\n");
+        assert!(open("utf16", b"\xff\xfea\x00\n\x00").editable().is_err());
+        assert!(open("cr", b"a\rb\r").editable().is_err());
+    }
+}
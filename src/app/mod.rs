@@ -2,6 +2,27 @@ mod app;
 mod undotree;
 mod ui;
 mod treesitter;
+mod commandline;
+mod options;
+mod keys;
+mod excommand;
+mod usercommand;
+mod fileio;
+mod largefile;
+mod hex;
+mod explorer;
+mod prompt;
+mod checktime;
+mod swap;
+mod viewport;
+mod window;
+mod statusline;
+mod colorscheme;
+mod signs;
+mod display;
+mod render;
 pub use app::*;
 pub use ui::*;
 pub use undotree::Cursor;
+pub use commandline::LineEditor;
+pub use swap::list_swap_files;
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use crate::app::display::ListChars;
+
+#[derive(Clone, PartialEq)]
+pub enum OptionValue {
+    Bool(bool),
+    Number(usize),
+    String(String),
+}
+
+impl std::fmt::Display for OptionValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptionValue::Bool(b) => write!(f, "{}", b),
+            OptionValue::Number(n) => write!(f, "{}", n),
+            OptionValue::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+pub struct OptionDef {
+    pub name: &'static str,
+    pub short: &'static str,
+    pub local: bool,
+    pub default: OptionValue,
+    pub values: &'static [&'static str],
+}
+
+fn definitions() -> Vec<OptionDef> {
+    let def = |name, short, local, default| OptionDef { name, short, local, default, values: &[] };
+    let choice = |name, short, local, default: &str, values| OptionDef { name, short, local, default: OptionValue::String(default.to_owned()), values };
+    vec![
+        def("autoread", "ar", true, OptionValue::Bool(false)),
+        def("bomb", "bomb", true, OptionValue::Bool(false)),
+        def("endofline", "eol", true, OptionValue::Bool(true)),
+        def("cursorline", "cul", false, OptionValue::Bool(false)),
+        def("expandtab", "et", true, OptionValue::Bool(false)),
+        choice("fileencoding", "fenc", true, "utf-8", &["utf-8", "utf-16", "utf-16le", "latin1"]),
+        choice("fileformat", "ff", true, "unix", &["unix", "dos", "mac"]),
+        def("fixendofline", "fixeol", true, OptionValue::Bool(false)),
+        def("foldcolumn", "fdc", false, OptionValue::Number(0)),
+        def("history", "hi", false, OptionValue::Number(200)),
+        def("ignorecase", "ic", false, OptionValue::Bool(false)),
+        def("largefile", "lf", false, OptionValue::Number(100)),
+        def("linebreak", "lbr", false, OptionValue::Bool(false)),
+        def("list", "list", false, OptionValue::Bool(false)),
+        def("listchars", "lcs", false, OptionValue::String("tab:> ,trail:-,nbsp:+".to_owned())),
+        def("number", "nu", false, OptionValue::Bool(true)),
+        def("numberwidth", "nuw", false, OptionValue::Number(4)),
+        def("readonly", "ro", true, OptionValue::Bool(false)),
+        def("relativenumber", "rnu", false, OptionValue::Bool(false)),
+        def("scrolloff", "so", false, OptionValue::Number(0)),
+        def("shiftwidth", "sw", true, OptionValue::Number(8)),
+        def("showbreak", "sbr", false, OptionValue::String(String::new())),
+        def("sidescrolloff", "siso", false, OptionValue::Number(0)),
+        choice("signcolumn", "scl", false, "auto", &["auto", "yes", "no"]),
+        def("smartcase", "scs", false, OptionValue::Bool(false)),
+        def("statusline", "stl", false, OptionValue::String("%M  %f %m%r%=%k  %y  %e  %o  %l:%c  %p%%".to_owned())),
+        def("swapfile", "swf", true, OptionValue::Bool(true)),
+        def("syntax", "syn", true, OptionValue::Bool(true)),
+        def("tabstop", "ts", true, OptionValue::Number(8)),
+        def("updatecount", "uc", false, OptionValue::Number(200)),
+        def("updatetime", "ut", false, OptionValue::Number(4000)),
+        def("wrap", "wrap", false, OptionValue::Bool(false)),
+    ]
+}
+
+pub type LocalOptions = HashMap<&'static str, OptionValue>;
+
+pub struct Options {
+    defs: Vec<OptionDef>,
+    values: HashMap<&'static str, OptionValue>,
+}
+
+impl Options {
+    pub fn new() -> Options {
+        let defs = definitions();
+        let values = defs.iter().map(|def| (def.name, def.default.clone())).collect();
+        Options {
+            defs,
+            values,
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.defs.iter().map(|def| def.name)
+    }
+
+    fn find(&self, name: &str) -> Option<&OptionDef> {
+        self.defs.iter().find(|def| def.name == name || def.short == name)
+    }
+
+    pub fn get<'a>(&'a self, local: Option<&'a LocalOptions>, name: &str) -> &'a OptionValue {
+        let def = self.find(name).expect("unknown option");
+        if def.local && let Some(value) = local.and_then(|local| local.get(def.name)) {
+            return value;
+        }
+        &self.values[def.name]
+    }
+
+    pub fn bool(&self, local: Option<&LocalOptions>, name: &str) -> bool {
+        matches!(self.get(local, name), OptionValue::Bool(true))
+    }
+
+    pub fn number(&self, local: Option<&LocalOptions>, name: &str) -> usize {
+        match self.get(local, name) {
+            OptionValue::Number(n) => *n,
+            _ => 0,
+        }
+    }
+
+    pub fn string<'a>(&'a self, local: Option<&'a LocalOptions>, name: &str) -> &'a str {
+        match self.get(local, name) {
+            OptionValue::String(s) => s,
+            _ => "",
+        }
+    }
+
+    pub fn set(&mut self, mut local: Option<&mut LocalOptions>, args: &str, only_local: bool) -> Result<Vec<String>, String> {
+        let mut shown = vec![];
+        if args.trim().is_empty() {
+            for def in &self.defs {
+                let value = self.get(local.as_deref(), def.name);
+                if *value != def.default {
+                    shown.push(format_option(def.name, value));
+                }
+            }
+            return Ok(shown);
+        }
+        for arg in split_args(args) {
+            let arg = arg.as_str();
+            let (name, value) = match arg.find(['=', ':']) {
+                Some(i) => (&arg[..i], Some(&arg[i + 1..])),
+                None => (arg, None),
+            };
+            let (name, action) = if let Some(name) = name.strip_suffix('?') {
+                (name, '?')
+            } else if let Some(name) = name.strip_suffix('!') {
+                (name, '!')
+            } else if let Some(name) = name.strip_suffix('&') {
+                (name, '&')
+            } else {
+                (name, ' ')
+            };
+            let (def, negated) = match self.find(name) {
+                Some(def) => (def, None),
+                None => match name.strip_prefix("no").and_then(|n| self.find(n)) {
+                    Some(def) => (def, Some(false)),
+                    None => match name.strip_prefix("inv").and_then(|n| self.find(n)) {
+                        Some(def) => (def, Some(true)),
+                        None => return Err(format!("E518: Unknown option: {}", name)),
+                    },
+                },
+            };
+            let current = self.get(local.as_deref(), def.name).clone();
+            let new = match (action, value, negated, &current) {
+                ('?', _, _, _) => {
+                    shown.push(format_option(def.name, &current));
+                    continue;
+                },
+                ('&', _, _, _) => def.default.clone(),
+                ('!', _, _, OptionValue::Bool(b)) | (_, None, Some(true), OptionValue::Bool(b)) => OptionValue::Bool(!b),
+                (_, None, Some(false), OptionValue::Bool(_)) => OptionValue::Bool(false),
+                (_, None, None, OptionValue::Bool(_)) => OptionValue::Bool(true),
+                (_, None, None, _) => {
+                    shown.push(format_option(def.name, &current));
+                    continue;
+                },
+                (_, Some(value), None, OptionValue::Number(_)) => match value.parse() {
+                    Ok(n) => OptionValue::Number(n),
+                    Err(_) => return Err(format!("E521: Number required after =: {}", arg)),
+                },
+                (_, Some(value), None, OptionValue::String(_)) if def.name == "listchars" && ListChars::parse(value).is_none() => {
+                    return Err(format!("E474: Invalid argument: {}", arg));
+                },
+                (_, Some(value), None, OptionValue::String(_)) if def.values.is_empty() || def.values.contains(&value) => OptionValue::String(value.to_owned()),
+                _ => return Err(format!("E474: Invalid argument: {}", arg)),
+            };
+            let name = def.name;
+            let has_local = def.local && local.is_some();
+            if has_local && let Some(local) = local.as_deref_mut() {
+                local.insert(name, new.clone());
+            }
+            if !(has_local && only_local) {
+                self.values.insert(name, new);
+            }
+        }
+        Ok(shown)
+    }
+}
+
+pub fn escape_arg(arg: &str) -> String {
+    arg.replace('\\', "\\\\").replace(' ', "\\ ")
+}
+
+pub fn split_args(args: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut chars = args.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next @ (' ' | '\\')) => word.push(next),
+                Some(next) => {
+                    word.push(c);
+                    word.push(next);
+                },
+                None => word.push(c),
+            },
+            c if c.is_whitespace() => if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            },
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn format_option(name: &str, value: &OptionValue) -> String {
+    match value {
+        OptionValue::Bool(true) => format!("  {}", name),
+        OptionValue::Bool(false) => format!("no{}", name),
+        value => format!("  {}={}", name, value),
+    }
+}
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+use crossterm::event::{KeyCode, KeyEvent};
+
+use crate::app::{App, CurrentEditing, CurrentScreenMode};
+
+pub enum PromptAction {
+    Reload(usize),
+    Keep(usize),
+    Diff(usize),
+    ReadOnly(usize),
+    EditAnyway(usize),
+    Recover(usize, PathBuf),
+    DeleteSwap(usize, PathBuf),
+    QuitFile(usize),
+    DeletePath(PathBuf),
+    Cancel,
+}
+
+pub struct Prompt {
+    pub message: String,
+    pub choices: Vec<(char, &'static str, PromptAction)>,
+    pub default: usize,
+}
+
+impl App {
+    pub fn prompt_key(&mut self, key: KeyEvent) {
+        let prompt = match std::mem::replace(&mut self.current_editing, CurrentEditing::Selecting) {
+            CurrentEditing::Prompt(prompt) => prompt,
+            editing => {
+                self.current_editing = editing;
+                return;
+            },
+        };
+        let choice = match key.code {
+            KeyCode::Esc | KeyCode::Enter => Some(prompt.default),
+            KeyCode::Char(c) => prompt.choices.iter().position(|(key, _, _)| *key == c.to_ascii_lowercase()),
+            _ => None,
+        };
+        match choice {
+            Some(choice) => {
+                let action = prompt.choices.into_iter().nth(choice).map(|(_, _, action)| action);
+                if let Some(action) = action {
+                    self.run_prompt_action(action);
+                }
+            },
+            None => self.current_editing = CurrentEditing::Prompt(prompt),
+        }
+    }
+
+    fn run_prompt_action(&mut self, action: PromptAction) {
+        match action {
+            PromptAction::Reload(index) => self.reload_file(index, None),
+            PromptAction::Keep(index) => self.keep_file(index),
+            PromptAction::Diff(index) => self.diff_file(index),
+            PromptAction::ReadOnly(index) => self.open_read_only(index),
+            PromptAction::EditAnyway(index) => self.edit_anyway(index),
+            PromptAction::Recover(index, path) => self.recover_file(index, path),
+            PromptAction::DeleteSwap(index, path) => self.delete_swap(index, &path),
+            PromptAction::QuitFile(index) => {
+                self.current_screen = CurrentScreenMode::File(index);
+                let _ = self.quit_file(true);
+            },
+            PromptAction::DeletePath(path) => self.delete_path(&path),
+            PromptAction::Cancel => (),
+        }
+        self.check_swap_files();
+        self.check_time();
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use ratatui::text::Line;
+
+#[derive(Default)]
+pub struct LineIndex {
+    version: Option<u64>,
+    starts: Vec<usize>,
+    len: usize,
+    newlines: usize,
+}
+
+impl LineIndex {
+    pub fn update(&mut self, text: &str, version: u64) {
+        if self.version == Some(version) {
+            return;
+        }
+        self.version = Some(version);
+        self.len = text.len();
+        self.starts.clear();
+        if !text.is_empty() {
+            self.starts.push(0);
+        }
+        self.newlines = 0;
+        for (i, _) in text.match_indices('\n') {
+            self.newlines += 1;
+            if i + 1 < text.len() {
+                self.starts.push(i + 1);
+            }
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.starts.len()
+    }
+
+    pub fn newlines(&self) -> usize {
+        self.newlines
+    }
+
+    pub fn start(&self, line: usize) -> usize {
+        self.starts.get(line).copied().unwrap_or(self.len)
+    }
+
+    pub fn line<'a>(&self, text: &'a str, num: usize) -> &'a str {
+        let Some(&start) = self.starts.get(num) else { return "" };
+        let end = self.starts.get(num + 1).copied().unwrap_or(self.len);
+        let line = &text[start..end];
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        line.strip_suffix('\r').unwrap_or(line)
+    }
+
+    pub fn position(&self, text: &str, cursor: usize) -> (usize, usize) {
+        let mut cursor = cursor.min(text.len());
+        while !text.is_char_boundary(cursor) {
+            cursor -= 1;
+        }
+        if cursor == self.len && text.ends_with('\n') {
+            return (self.newlines, 0);
+        }
+        let line = self.starts.partition_point(|start| *start <= cursor).saturating_sub(1);
+        let start = self.starts.get(line).copied().unwrap_or(0);
+        (line, text[start..cursor].chars().count())
+    }
+
+    pub fn index(&self, text: &str, (line, column): (usize, usize)) -> usize {
+        let line = line.min(self.newlines);
+        let start = self.start(line);
+        let text = self.line(text, line);
+        start + text.char_indices().nth(column).map_or(text.len(), |(i, _)| i)
+    }
+}
+
+#[derive(Default)]
+pub struct LineCache {
+    previous: HashMap<String, Line<'static>>,
+    current: HashMap<String, Line<'static>>,
+}
+
+impl LineCache {
+    pub fn get(&mut self, line: &str, build: impl FnOnce() -> Line<'static>) -> Line<'static> {
+        if let Some(cached) = self.current.get(line) {
+            return cached.clone();
+        }
+        let cached = self.previous.remove(line).unwrap_or_else(build);
+        self.current.insert(line.to_owned(), cached.clone());
+        cached
+    }
+
+    pub fn end_frame(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.current.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::LineIndex;
+    use crate::app::{ui, App, CurrentScreenMode, File};
+
+    fn frame_time(lines: usize) -> Duration {
+        const FRAMES: u32 = 200;
+        let text: String = (0..lines)
+            .map(|i| format!("    let value_{} = Some(\"{}\".len() + {}); // line {}\n", i, i, i * 7, i))
+            .collect();
+        let mut app = App::new();
+        let mut file = File::new("bench.rs".to_owned());
+        file.undo_tree.replace_text(text, 0);
+        app.files.push(file);
+        app.current_screen = CurrentScreenMode::File(0);
+        app.sync_windows();
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        terminal.draw(|frame| ui(&app, frame)).unwrap();
+        let start = Instant::now();
+        for frame in 0..FRAMES {
+            if frame % 10 == 0 {
+                let line = (frame as usize * 7919) % lines;
+                app.files[0].undo_tree.set_cursor(line * 48);
+            }
+            terminal.draw(|frame| ui(&app, frame)).unwrap();
+        }
+        start.elapsed() / FRAMES
+    }
+
+    #[test]
+    fn index_inverts_position() {
+        let text = "one\ntwö\n\nfour\n";
+        let mut index = LineIndex::default();
+        index.update(text, 0);
+        for cursor in (0..=text.len()).filter(|&i| text.is_char_boundary(i)) {
+            assert_eq!(index.index(text, index.position(text, cursor)), cursor);
+        }
+        assert_eq!(index.index(text, (1, 10)), 8);
+        assert_eq!(index.index(text, (10, 0)), text.len());
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with cargo test --release -- --ignored"]
+    fn render_cost_stays_flat() {
+        let small = frame_time(1_000);
+        for lines in [10_000, 100_000, 1_000_000] {
+            let large = frame_time(lines);
+            println!("{:>10} lines: {:>10.2?} per frame ({:.2?} for 1000 lines)", lines, large, small);
+            assert!(large < small * 3, "drawing {} lines takes {:?} per frame, {:?} for 1000 lines", lines, large, small);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use crate::app::{checktime::{diff_lines, Change}, colorscheme::{group, Group}, undotree::UndoTree, App, CurrentScreenMode, File};
+
+pub struct Sign {
+    pub line: usize,
+    pub text: String,
+    pub group: Group,
+    pub source: String,
+    pub priority: u8,
+}
+
+#[derive(Default)]
+pub struct Signs {
+    signs: Vec<Sign>,
+}
+
+impl Signs {
+    pub fn place(&mut self, sign: Sign) {
+        self.signs.retain(|placed| placed.line != sign.line || placed.source != sign.source);
+        self.signs.push(sign);
+    }
+
+    pub fn unplace(&mut self, source: &str, line: Option<usize>) {
+        self.signs.retain(|sign| sign.source != source || line.is_some_and(|line| sign.line != line));
+    }
+
+    pub fn at(&self, line: usize) -> Option<&Sign> {
+        self.signs.iter().filter(|sign| sign.line == line).max_by_key(|sign| sign.priority)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Sign> {
+        self.signs.iter()
+    }
+
+    pub fn shift(&mut self, line: usize, removed: usize, added: usize) {
+        for sign in &mut self.signs {
+            if sign.line >= line + removed {
+                sign.line = sign.line + added - removed;
+            } else if sign.line >= line + added {
+                sign.line = line + added.saturating_sub(1);
+            }
+        }
+    }
+
+    pub fn move_lines(&mut self, start: usize, end: usize, insert_at: usize) {
+        let count = end + 1 - start;
+        for sign in &mut self.signs {
+            sign.line = match sign.line {
+                line if (start..=end).contains(&line) => insert_at + line - start,
+                line => {
+                    let line = if line > end { line - count } else { line };
+                    if line >= insert_at { line + count } else { line }
+                },
+            };
+        }
+    }
+
+    pub fn clamp(&mut self, lines: usize) {
+        for sign in &mut self.signs {
+            sign.line = sign.line.min(lines.saturating_sub(1));
+        }
+    }
+
+    pub fn follow(&mut self, old: &str, new: &str) {
+        let old: Vec<&str> = old.split('\n').collect();
+        let new: Vec<&str> = new.split('\n').collect();
+        let (mut line, mut removed, mut added) = (0, 0, 0);
+        for change in diff_lines(&old, &new) {
+            match change {
+                Change::Same => {
+                    if removed + added > 0 {
+                        self.shift(line, removed, added);
+                        line += added;
+                        (removed, added) = (0, 0);
+                    }
+                    line += 1;
+                },
+                Change::Removed => removed += 1,
+                Change::Added => added += 1,
+            }
+        }
+        if removed + added > 0 {
+            self.shift(line, removed, added);
+        }
+        self.clamp(new.len());
+    }
+}
+
+impl File {
+    pub fn follow_signs(&mut self, edit: impl FnOnce(&mut UndoTree)) {
+        if self.signs.is_empty() {
+            edit(&mut self.undo_tree);
+            return;
+        }
+        let old = self.text();
+        edit(&mut self.undo_tree);
+        self.signs.follow(&old, &self.text());
+    }
+
+    pub fn cursor_position(&self) -> Option<(usize, usize)> {
+        self.lines().map(|(text, cursor, index)| index.position(&text, cursor))
+    }
+
+    pub fn new_line(&mut self) {
+        if !self.signs.is_empty() && let Some((line, column)) = self.cursor_position() {
+            self.signs.shift(if column == 0 { line } else { line + 1 }, 0, 1);
+        }
+        self.undo_tree.add_newspace();
+    }
+
+    pub fn del_char(&mut self) {
+        if !self.signs.is_empty() && let Some((line, 0)) = self.cursor_position() && line > 0 {
+            self.signs.shift(line - 1, 2, 1);
+        }
+        self.undo_tree.del_char();
+    }
+}
+
+impl App {
+    pub fn sign_command(&mut self, args: &str) {
+        let CurrentScreenMode::File(index) = self.current_screen else { return };
+        let mut args = args.split_whitespace();
+        match args.next() {
+            Some("place") => {
+                let (Some(line), Some(text)) = (args.next(), args.next()) else {
+                    self.error("E159: Missing sign line and text".to_owned());
+                    return;
+                };
+                let Some(line) = line.parse::<usize>().ok().filter(|line| *line > 0) else {
+                    self.error(format!("E474: Invalid argument: {}", line));
+                    return;
+                };
+                if text.chars().count() > 2 {
+                    self.error(format!("E239: Invalid sign text: {}", text));
+                    return;
+                }
+                let name = args.next().unwrap_or("SignColumn");
+                let Some(group) = group(name) else {
+                    self.error(format!("E411: Highlight group not found: {}", name));
+                    return;
+                };
+                self.files[index].signs.place(Sign { line: line - 1, text: text.to_owned(), group, source: "user".to_owned(), priority: 10 });
+            },
+            Some("unplace") => match args.next() {
+                Some("*") | None => self.files[index].signs.unplace("user", None),
+                Some(line) => match line.parse::<usize>() {
+                    Ok(line) if line > 0 => self.files[index].signs.unplace("user", Some(line - 1)),
+                    _ => self.error(format!("E474: Invalid argument: {}", line)),
+                },
+            },
+            Some("list") => {
+                let mut signs: Vec<(usize, String)> = self.files[index].signs
+                    .iter()
+                    .map(|sign| (sign.line, format!("    line={:<6} text={:<3} source={:<8} priority={}", sign.line + 1, sign.text, sign.source, sign.priority)))
+                    .collect();
+                signs.sort();
+                self.info(format!("--- Signs --- {}", self.files[index].name));
+                for (_, sign) in signs {
+                    self.info(sign);
+                }
+                self.show_messages = true;
+            },
+            Some(subcommand) => self.error(format!("E160: Unknown sign command: {}", subcommand)),
+            None => self.error("E471: Argument required".to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signs(lines: &[usize]) -> Signs {
+        let mut signs = Signs::default();
+        for (i, line) in lines.iter().enumerate() {
+            signs.place(Sign { line: *line, text: ">".to_owned(), group: Group::SignColumn, source: i.to_string(), priority: 10 });
+        }
+        signs
+    }
+
+    fn lines(signs: &Signs) -> Vec<usize> {
+        signs.iter().map(|sign| sign.line).collect()
+    }
+
+    #[test]
+    fn shift_moves_signs_below_the_change() {
+        let mut placed = signs(&[0, 2, 5]);
+        placed.shift(1, 0, 2);
+        assert_eq!(lines(&placed), [0, 4, 7]);
+        placed.shift(1, 2, 0);
+        assert_eq!(lines(&placed), [0, 2, 5]);
+    }
+
+    #[test]
+    fn shift_keeps_signs_of_removed_lines_on_the_block() {
+        let mut placed = signs(&[1, 2, 3, 4]);
+        placed.shift(1, 3, 0);
+        assert_eq!(lines(&placed), [1, 1, 1, 1]);
+        let mut placed = signs(&[1, 2, 3]);
+        placed.shift(1, 2, 1);
+        assert_eq!(lines(&placed), [1, 1, 2]);
+    }
+
+    #[test]
+    fn move_lines_moves_the_block_and_the_lines_in_between() {
+        let mut placed = signs(&[0, 1, 2, 3, 4]);
+        placed.move_lines(1, 2, 2);
+        assert_eq!(lines(&placed), [0, 2, 3, 1, 4]);
+        let mut placed = signs(&[0, 1, 2, 3, 4]);
+        placed.move_lines(3, 4, 0);
+        assert_eq!(lines(&placed), [2, 3, 4, 0, 1]);
+    }
+
+    #[test]
+    fn follow_shifts_each_changed_block() {
+        let mut placed = signs(&[2, 3, 5]);
+        placed.follow("a\nx\nb\nc\nx\nd", "a\nb\nc\nd");
+        assert_eq!(lines(&placed), [1, 2, 3]);
+        let mut placed = signs(&[1, 3]);
+        placed.follow("a\nb\nc\nd", "new\na\nb\nc\nnew\nd");
+        assert_eq!(lines(&placed), [2, 5]);
+    }
+
+    #[test]
+    fn clamp_keeps_signs_inside_the_buffer() {
+        let mut placed = signs(&[0, 3]);
+        placed.shift(2, 2, 0);
+        placed.clamp(2);
+        assert_eq!(lines(&placed), [0, 1]);
+    }
+}
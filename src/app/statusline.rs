@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use crate::app::{hex::ROW, App, CurrentEditing};
+
+pub fn filetype(name: &str) -> &str {
+    match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+        Some("rs") => "rust",
+        Some("py") => "python",
+        Some("js" | "mjs") => "javascript",
+        Some("ts") => "typescript",
+        Some("c" | "h") => "c",
+        Some("cc" | "cpp" | "hpp") => "cpp",
+        Some("go") => "go",
+        Some("sh" | "bash") => "sh",
+        Some("md") => "markdown",
+        Some("toml") => "toml",
+        Some("json") => "json",
+        Some("yml" | "yaml") => "yaml",
+        Some("html" | "htm") => "html",
+        Some("css") => "css",
+        Some("txt") => "text",
+        Some(ext) => ext,
+        None => "",
+    }
+}
+
+impl App {
+    pub fn mode_name(&self, index: usize) -> &'static str {
+        let file = &self.files[index];
+        match &self.current_editing {
+            CurrentEditing::Page => match &file.hex {
+                Some(hex) if hex.insert => "Hex insert",
+                Some(_) => "Hex replace",
+                None => "Page",
+            },
+            CurrentEditing::Replace => "Replace",
+            CurrentEditing::Command(_) => "Command",
+            CurrentEditing::Search(_) => "Search",
+            CurrentEditing::Prompt(_) => "Prompt",
+            _ if file.hex.is_some() => "Hex",
+            _ if file.explorer.is_some() => "Explorer",
+            _ if file.large.is_some() => "Large file",
+            _ => "Selecting",
+        }
+    }
+
+    fn pending_keys(&self) -> String {
+        match self.current_editing {
+            CurrentEditing::Listening(c) if c.is_ascii_control() => format!("^{}", (c as u8 + b'@') as char),
+            CurrentEditing::Listening(c) => c.to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn position(&self, index: usize) -> (usize, usize, usize) {
+        let file = &self.files[index];
+        if let Some(large) = &file.large {
+            (large.line, large.cursor_column(), large.line_count())
+        } else if let Some(hex) = &file.hex {
+            (hex.cursor / ROW, hex.cursor % ROW, hex.bytes.len() / ROW + 1)
+        } else if let Some(explorer) = &file.explorer {
+            (explorer.selected, 0, explorer.entries.len())
+        } else {
+            match file.lines() {
+                Some((text, cursor, index)) => {
+                    let (line, column) = index.position(&text, cursor);
+                    (line, column, index.newlines() + 1)
+                },
+                None => (0, 0, 1),
+            }
+        }
+    }
+
+    pub fn status_line(&self, index: usize) -> (String, String) {
+        let file = &self.files[index];
+        let local = Some(&file.local_options);
+        let (line, column, lines) = self.position(index);
+        let mut sides = (String::new(), String::new());
+        let mut right = false;
+        let mut chars = self.options.string(None, "statusline").chars();
+        while let Some(c) = chars.next() {
+            let side = if right { &mut sides.1 } else { &mut sides.0 };
+            if c != '%' {
+                side.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('f') => side.push_str(&file.name),
+                Some('m') => if file.is_modified() {
+                    side.push_str("[+]");
+                },
+                Some('r') => if self.options.bool(local, "readonly") {
+                    side.push_str("[RO]");
+                },
+                Some('y') => if !filetype(&file.name).is_empty() {
+                    side.push_str(&format!("[{}]", filetype(&file.name)));
+                },
+                Some('e') => side.push_str(if file.hex.is_some() { "binary" } else { self.options.string(local, "fileencoding") }),
+                Some('o') => side.push_str(self.options.string(local, "fileformat")),
+                Some('l') => side.push_str(&(line + 1).to_string()),
+                Some('c') => side.push_str(&(column + 1).to_string()),
+                Some('L') => side.push_str(&lines.to_string()),
+                Some('p') => side.push_str(&((line + 1) * 100 / lines.max(1)).to_string()),
+                Some('k') => side.push_str(&self.pending_keys()),
+                Some('M') => side.push_str(self.mode_name(index)),
+                Some('=') => right = true,
+                Some(c) => side.push(c),
+                None => side.push('%'),
+            }
+        }
+        sides
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use crate::app::{
+    largefile::LargeFile,
+    options::OptionValue,
+    prompt::{Prompt, PromptAction},
+    App, CurrentEditing, CurrentScreenMode,
+};
+
+const EXTENSIONS: [&str; 6] = ["swp", "swo", "swn", "swm", "swl", "swk"];
+
+pub struct SwapInfo {
+    pub pid: u32,
+    pub file: String,
+    pub large: Option<usize>,
+    pub text: Option<String>,
+}
+
+impl SwapInfo {
+    pub fn running(&self) -> bool {
+        unsafe { libc::kill(self.pid as libc::pid_t, 0) == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+    }
+}
+
+pub fn swap_path(name: &str, n: usize) -> PathBuf {
+    let path = Path::new(name);
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}", file_name, EXTENSIONS[n]))
+}
+
+fn reserve_swap(name: &str) -> Option<PathBuf> {
+    (0..EXTENSIONS.len()).map(|n| swap_path(name, n)).find(|path| {
+        let created = fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path);
+        match created.and_then(|mut swap| write_swap(&mut swap, name, None, None)) {
+            Ok(_) => true,
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => false,
+            Err(_) => {
+                let _ = fs::remove_file(path);
+                false
+            },
+        }
+    })
+}
+
+fn find_swap(name: &str) -> Option<(PathBuf, SwapInfo)> {
+    (0..EXTENSIONS.len())
+        .map(|n| swap_path(name, n))
+        .find_map(|path| read_swap(&path).ok().map(|info| (path, info)))
+}
+
+pub fn read_swap(path: &Path) -> io::Result<SwapInfo> {
+    let content = fs::read_to_string(path)?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a Rime swap file");
+    let (header, text) = match content.split_once("\n\n") {
+        Some((header, text)) => (header, Some(text.to_owned())),
+        None => (content.as_str(), None),
+    };
+    let mut lines = header.lines();
+    if lines.next() != Some("rime swap") {
+        return Err(invalid());
+    }
+    let mut info = SwapInfo { pid: 0, file: String::new(), large: None, text };
+    for line in lines {
+        match line.split_once(": ") {
+            Some(("pid", pid)) => info.pid = pid.parse().map_err(|_| invalid())?,
+            Some(("file", file)) => info.file = file.to_owned(),
+            Some(("large", len)) => info.large = Some(len.parse().map_err(|_| invalid())?),
+            _ => (),
+        }
+    }
+    Ok(info)
+}
+
+fn write_swap(swap: &mut fs::File, name: &str, large: Option<usize>, text: Option<&str>) -> io::Result<()> {
+    let file = fs::canonicalize(name).unwrap_or_else(|_| PathBuf::from(name));
+    write!(swap, "rime swap\npid: {}\nfile: {}\n", std::process::id(), file.display())?;
+    if let Some(len) = large {
+        writeln!(swap, "large: {}", len)?;
+    }
+    if let Some(text) = text {
+        write!(swap, "\n{}", text)?;
+    }
+    swap.sync_all()
+}
+
+fn update_swap(path: &Path, name: &str, large: Option<usize>, text: Option<&str>) -> io::Result<()> {
+    let mut swap = fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?;
+    write_swap(&mut swap, name, large, text)
+}
+
+pub fn list_swap_files(dir: &Path) -> Vec<(PathBuf, SwapInfo)> {
+    let mut found: Vec<(PathBuf, SwapInfo)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| EXTENSIONS.iter().any(|swap| ext == *swap)))
+        .filter_map(|path| read_swap(&path).ok().map(|info| (path, info)))
+        .collect();
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+    found
+}
+
+fn hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl App {
+    pub fn check_swap_files(&mut self) {
+        if let CurrentEditing::Prompt(_) = self.current_editing {
+            return;
+        }
+        for index in 0..self.files.len() {
+            let file = &self.files[index];
+            if file.swap.is_some() || file.explorer.is_some() || file.scratch || !self.options.bool(Some(&file.local_options), "swapfile") {
+                continue;
+            }
+            let name = file.name.clone();
+            let Some((path, info)) = find_swap(&name) else {
+                self.files[index].swap = reserve_swap(&name);
+                continue;
+            };
+            if info.text.is_none() && !info.running() && fs::remove_file(&path).is_ok() {
+                self.files[index].swap = reserve_swap(&name);
+                continue;
+            }
+            if self.recover {
+                self.recover_file(index, path);
+                continue;
+            }
+            let process = if info.running() { " (still running)" } else { "" };
+            self.current_screen = CurrentScreenMode::File(index);
+            self.current_editing = CurrentEditing::Prompt(Prompt {
+                message: format!("E325: ATTENTION: Found a swap file \"{}\" for \"{}\" owned by process {}{}", path.display(), name, info.pid, process),
+                choices: vec![
+                    ('o', "Open Read-Only", PromptAction::ReadOnly(index)),
+                    ('e', "Edit anyway", PromptAction::EditAnyway(index)),
+                    ('r', "Recover", PromptAction::Recover(index, path.clone())),
+                    ('d', "Delete it", PromptAction::DeleteSwap(index, path)),
+                    ('q', "Quit", PromptAction::QuitFile(index)),
+                ],
+                default: 0,
+            });
+            return;
+        }
+    }
+
+    pub fn recover_file(&mut self, index: usize, path: PathBuf) {
+        let info = match read_swap(&path) {
+            Ok(info) => info,
+            Err(error) => {
+                self.files[index].swap = reserve_swap(&self.files[index].name);
+                self.error(format!("E306: Cannot open \"{}\": {}", path.display(), error));
+                return;
+            },
+        };
+        let name = self.files[index].name.clone();
+        let Some(text) = info.text else {
+            self.files[index].swap = reserve_swap(&name);
+            self.info(format!("\"{}\" has no changes to recover", path.display()));
+            return;
+        };
+        let file = &mut self.files[index];
+        match (&mut file.large, info.large) {
+            (Some(large), Some(len)) => if large.len() != len || !large.recover(&text) {
+                file.swap = reserve_swap(&name);
+                self.error(format!("E308: \"{}\" was changed since the swap file was written, can't recover", name));
+                return;
+            },
+            (None, None) => {
+                let cursor = file.undo_tree.show_current_node().map(|(_, cursor)| cursor).unwrap_or(0);
+                file.follow_signs(|undo_tree| undo_tree.replace_text(text.clone(), cursor));
+            },
+            _ => {
+                file.swap = reserve_swap(&name);
+                self.error(format!("E308: \"{}\" was changed since the swap file was written, can't recover", name));
+                return;
+            },
+        }
+        file.swap_hash = Some(hash(&text));
+        file.swap = Some(path);
+        self.info(format!("Recovery completed for \"{}\": check the changes, then write the file", name));
+    }
+
+    pub fn open_read_only(&mut self, index: usize) {
+        self.files[index].local_options.insert("readonly", OptionValue::Bool(true));
+        self.files[index].swap = reserve_swap(&self.files[index].name);
+    }
+
+    pub fn edit_anyway(&mut self, index: usize) {
+        self.files[index].swap = reserve_swap(&self.files[index].name);
+    }
+
+    pub fn delete_swap(&mut self, index: usize, path: &Path) {
+        match fs::remove_file(path) {
+            Ok(_) => self.files[index].swap = None,
+            Err(error) => {
+                self.error(format!("E305: Cannot delete \"{}\": {}", path.display(), error));
+                self.files[index].swap = reserve_swap(&self.files[index].name);
+            },
+        }
+    }
+
+    pub fn move_swap(&mut self, index: usize, old: &Path) {
+        let _ = fs::remove_file(old);
+        self.files[index].swap = reserve_swap(&self.files[index].name);
+        self.files[index].swap_hash = None;
+    }
+
+    pub fn write_swap_files(&mut self) {
+        for index in 0..self.files.len() {
+            let file = &self.files[index];
+            let Some(path) = file.swap.clone() else { continue };
+            if !self.options.bool(Some(&file.local_options), "swapfile") {
+                self.remove_swap(index);
+                continue;
+            }
+            let large = file.large.as_ref().map(LargeFile::len);
+            let text = match &file.large {
+                _ if !file.is_modified() || file.hex.is_some() => None,
+                Some(large) => Some(large.swap_text()),
+                None => Some(file.text()),
+            };
+            let text_hash = text.as_deref().map(hash);
+            if file.swap_hash == text_hash {
+                continue;
+            }
+            match update_swap(&path, &file.name, large, text.as_deref()) {
+                Ok(_) => self.files[index].swap_hash = text_hash,
+                Err(error) => {
+                    let message = format!("E303: Unable to write swap file \"{}\": {}", path.display(), error);
+                    self.files[index].local_options.insert("swapfile", OptionValue::Bool(false));
+                    self.remove_swap(index);
+                    self.error(message);
+                },
+            }
+        }
+    }
+
+    pub fn remove_swap(&mut self, index: usize) {
+        self.files[index].swap_hash = None;
+        if let Some(path) = self.files[index].swap.take() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    pub fn remove_swap_files(&mut self) {
+        for index in 0..self.files.len() {
+            self.remove_swap(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_slots_are_not_shared() {
+        let dir = std::env::temp_dir().join(format!("rime-swap-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let name = dir.join("file").to_string_lossy().into_owned();
+        let first = reserve_swap(&name).unwrap();
+        let second = reserve_swap(&name).unwrap();
+        assert_eq!((first.clone(), second.clone()), (swap_path(&name, 0), swap_path(&name, 1)));
+        let info = read_swap(&first).unwrap();
+        assert_eq!((info.pid, info.large, info.text), (std::process::id(), None, None));
+        update_swap(&second, &name, Some(10), Some("0 1 1\nline\n")).unwrap();
+        let info = read_swap(&second).unwrap();
+        assert_eq!((info.large, info.text.as_deref()), (Some(10), Some("0 1 1\nline\n")));
+        update_swap(&second, &name, None, None).unwrap();
+        assert_eq!(read_swap(&second).unwrap().text, None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,13 @@
-use ratatui::{layout::{Constraint, Direction, Layout}, prelude::Span, style::{Color, Style, Styled}, text::{Line, Text}, widgets::{Block, BorderType, Borders, Paragraph, Wrap}, Frame};
+use ratatui::{layout::{Constraint, Direction, Layout, Rect}, prelude::Span, style::{Style, Stylize}, text::{Line, Text}, widgets::{Block, BorderType, Borders, Clear, Paragraph}, Frame};
 
-use crate::{app::App, CurrentScreenMode, CurrentEditing};
+use std::ops::Range;
 
-use super::treesitter::Tree;
+use crossterm::cursor::SetCursorStyle;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{app::{App, File}, CurrentScreenMode, CurrentEditing};
+
+use super::{colorscheme::{Colorscheme, Group}, explorer::{EntryKind, Explorer}, hex::{HexView, ROW}, largefile::LargeFile, treesitter::highlight, viewport::WrapLayout, window::Window};
 
 pub fn ui(app: &App, frame: &mut Frame) {
     let chunks = Layout::default()
@@ -10,6 +15,7 @@ pub fn ui(app: &App, frame: &mut Frame) {
         .constraints([
             Constraint::Length(2),
             Constraint::Min(1),
+            Constraint::Length(if app.message.is_some() || matches!(app.current_editing, CurrentEditing::Prompt(_)) { 1 } else { 0 }),
             Constraint::Length(3)
         ])
         .split(frame.size());
@@ -17,15 +23,25 @@ pub fn ui(app: &App, frame: &mut Frame) {
     let title_block = Block::default()
         .borders(Borders::BOTTOM)
         .style(Style::default());
-    let mut title_str = vec![Span::styled("Rime | ", Style::default().fg(Color::Rgb(183, 65, 14)))]; 
+    let colors = &app.colors;
+    let mut title_str = vec![Span::styled("Rime | ", colors.get(Group::TabLine))]; 
     for file in &app.files {
         if let CurrentScreenMode::File(index) = app.current_screen {
             if app.files[index].name == file.name {
-                title_str.push(Span::styled(&file.name, Style::default().fg(Color::LightCyan)));
+                title_str.push(Span::styled(&file.name, colors.get(Group::TabLineSel)));
             } else {
-                title_str.push(Span::styled(&file.name, Style::default().fg(Color::Rgb(183, 65, 14))));
+                title_str.push(Span::styled(&file.name, colors.get(Group::TabLine)));
+            }
+            if file.new_file {
+                title_str.push(Span::styled(" [New]", colors.get(Group::TabLineSel)));
+            }
+            if app.options.bool(Some(&file.local_options), "readonly") {
+                title_str.push(Span::styled(" [RO]", colors.get(Group::TabLineSel)));
+            }
+            if file.is_modified() {
+                title_str.push(Span::styled(" [+]", colors.get(Group::TabLineSel)));
             }
-            title_str.push(Span::styled(" | ", Style::default().fg(Color::Rgb(183, 65, 14))));
+            title_str.push(Span::styled(" | ", colors.get(Group::TabLine)));
         }
     }
     let title = Paragraph::new(Line::from(title_str))
@@ -37,101 +53,438 @@ pub fn ui(app: &App, frame: &mut Frame) {
         .border_type(BorderType::Rounded)
         .style(Style::default());
 
-    let mode_str = match &app.current_screen {
-        CurrentScreenMode::File(_) => "File : ".to_owned() + &(match &app.current_editing {
-            CurrentEditing::Page => "Page".to_owned(),
-            CurrentEditing::Command(c) => format!("Command : {}", c),
-            CurrentEditing::Selecting => "Selecting".to_owned(),
-            CurrentEditing::Listening(c) => format!("Listening : {}", c),
-        }),
+    let mode_str = match app.current_screen {
+        CurrentScreenMode::File(index) => match &app.current_editing {
+            CurrentEditing::Command(_) => "File : Command : ".to_owned(),
+            CurrentEditing::Search(_) => "File : Search : /".to_owned(),
+            _ => {
+                let (left, right) = app.status_line(index);
+                let width = chunks[3].width.saturating_sub(2) as usize;
+                let padding = width.saturating_sub(left.chars().count() + right.chars().count()).max(1);
+                format!("{}{}{}", left, " ".repeat(padding), right)
+            },
+        },
         CurrentScreenMode::Main => "Main".to_owned(),
         CurrentScreenMode::Config => "Config".to_owned(),
     };
 
-    let mode = Paragraph::new(Text::styled(
-        mode_str,
-        Style::default().fg(Color::Rgb(183, 65, 14)),
-    ))
+    let mut cursor = None;
+    let mut mode_line = vec![Span::styled(mode_str.as_str(), colors.get(Group::StatusLine))];
+    if let CurrentScreenMode::File(_) = app.current_screen
+        && let CurrentEditing::Command(line) | CurrentEditing::Search(line) = &app.current_editing {
+        mode_line.push(Span::styled(line.text.as_str(), colors.get(Group::Normal)));
+        let x = chunks[3].x as usize + 1 + mode_str.width() + line.text[..line.cursor].width();
+        cursor = Some((x.min(u16::MAX as usize) as u16, chunks[3].y + 1));
+    }
+    let mode = Paragraph::new(Line::from(mode_line))
     .block(mode_block);
-    frame.render_widget(mode, chunks[2]);
-
-    match app.current_screen {
-       CurrentScreenMode::File(index) => {
-            if let Some((current_text, cursor_index)) = app.files[index].undo_tree.show_current_node() {
-                let sub_layout = Layout::default()
-                    .direction(Direction::Horizontal)
-                    .constraints([Constraint::Percentage(5), Constraint::Percentage(95)])
-                    .split(chunks[1]);
-                if let None = app.files[index].name.rfind(".rs") {
-                    let mut list_items = Vec::<Line>::new();
-                    let mut num_items = Vec::<Line>::new();
-                    let lines = current_text.lines();
-                    let mut cursor_line_index = 0;
-                    let mut found = false;
-                    for (num, line) in lines.enumerate() {
-                        let line_len = line.len() + 1;
-                        cursor_line_index += line_len;
-                        if cursor_line_index > cursor_index && !found {
-                            let index = cursor_index + line_len - cursor_line_index;
-                            num_items.push(Line::from(Span::styled((num + 1).to_string(), Style::default().fg(Color::LightCyan))));
-                            list_items.push(into_spans(line, Some(index)));
-                            found = true;
-                        } else {
-                            list_items.push(into_spans(line, None));
-                            num_items.push(Line::from(Span::styled((num + 1).to_string(), Style::default().fg(Color::Rgb(183, 65, 14)))));
-                        }
-                    }
-                    let final_text = Text::from(list_items);
-                    let final_lines = Text::from(num_items);
-                    frame.render_widget(final_lines, sub_layout[0]);
-                    frame.render_widget(final_text, sub_layout[1]);
-                } else {
-                    let tree = Tree::new(&current_text, cursor_index);
-                    let line_num = tree.into_numtext();
-                    let lines = tree.into_linetext();
-                    let mut cursor_line = 0;
-                    for (i, c) in current_text.chars().enumerate() {
-                        if c == '\n' {
-                            cursor_line += 1;
-                        }
-                        if i == cursor_index {
-                            break;
-                        }
-                    }
-                    frame.render_widget(Paragraph::new(Text::from(line_num)).scroll((cursor_line, 0)).centered(), sub_layout[0]);
-                    frame.render_widget(Paragraph::new(Text::from(lines)).scroll((cursor_line, 0)), sub_layout[1]);
-                }
+    frame.render_widget(mode, chunks[3]);
+
+    if let CurrentEditing::Prompt(prompt) = &app.current_editing {
+        let mut line = vec![Span::styled(format!("{} ", prompt.message), colors.get(Group::ErrorMsg))];
+        for (i, (key, label, _)) in prompt.choices.iter().enumerate() {
+            let style = if i == prompt.default {
+                colors.get(Group::Question).bold()
             } else {
+                colors.get(Group::Question)
             };
-        },
-        _ => (),
+            line.push(Span::styled(format!(" [{}]{}", key.to_ascii_uppercase(), &label[1..]), style));
+        }
+        frame.render_widget(Paragraph::new(Line::from(line)), chunks[2]);
+    } else if let Some(message) = &app.message {
+        frame.render_widget(Paragraph::new(message_line(message, colors)), chunks[2]);
+    }
+
+    if let CurrentEditing::Command(_) = app.current_editing && !app.command_line.completions.is_empty() {
+        let mut wildmenu = vec![];
+        for (i, completion) in app.command_line.completions.iter().enumerate() {
+            if i == app.command_line.completion_index {
+                wildmenu.push(Span::styled(completion, colors.get(Group::PmenuSel)));
+            } else {
+                wildmenu.push(Span::styled(completion, colors.get(Group::Pmenu)));
+            }
+            wildmenu.push(Span::raw("  "));
+        }
+        let area = Rect::new(chunks[3].x, chunks[3].y.saturating_sub(1), chunks[3].width, 1);
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(Line::from(wildmenu)), area);
+    }
+
+    if let CurrentScreenMode::File(index) = app.current_screen {
+        let mut areas = vec![];
+        let mut window_cursor = None;
+        if app.windows.is_empty() {
+            window_cursor = file_view(app, index, None, true, frame, chunks[1]);
+        } else {
+            app.window_tree.areas(chunks[1], &mut areas);
+        }
+        for (id, area) in areas {
+            let Some(window) = app.windows.get(id) else { continue };
+            window.area.set(area);
+            let focused = id == app.current_window;
+            let file = if focused { index } else { window.file };
+            if file >= app.files.len() {
+                continue;
+            }
+            if app.windows.len() > 1 {
+                let style = if focused { colors.get(Group::BorderSel) } else { colors.get(Group::Border) };
+                let block = Block::default()
+                    .title(app.files[file].name.as_str())
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .style(style);
+                let inner = block.inner(area);
+                frame.render_widget(block, area);
+                window_cursor = file_view(app, file, Some(window), focused, frame, inner).or(window_cursor);
+            } else {
+                window_cursor = file_view(app, file, Some(window), focused, frame, area).or(window_cursor);
+            }
+        }
+        if !matches!(app.current_editing, CurrentEditing::Prompt(_)) {
+            cursor = cursor.or(window_cursor);
+        }
+    }
+
+    if app.show_messages {
+        let area = chunks[1];
+        let height = area.height.saturating_sub(2) as usize;
+        let messages: Vec<Line> = app.messages
+            .iter()
+            .skip(app.messages.len().saturating_sub(height))
+            .map(|message| message_line(message, colors))
+            .collect();
+        let block = Block::default()
+            .title("Messages")
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .style(colors.get(Group::Border));
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(messages).block(block), area);
+    } else if let Some((x, y)) = cursor {
+        frame.set_cursor(x, y);
+    }
+}
+
+pub fn cursor_style(app: &App) -> SetCursorStyle {
+    let CurrentScreenMode::File(index) = app.current_screen else {
+        return SetCursorStyle::DefaultUserShape;
     };
+    match &app.current_editing {
+        CurrentEditing::Page => match &app.files[index].hex {
+            Some(hex) if !hex.insert => SetCursorStyle::SteadyUnderScore,
+            _ => SetCursorStyle::SteadyBar,
+        },
+        CurrentEditing::Replace => SetCursorStyle::SteadyUnderScore,
+        CurrentEditing::Command(_) | CurrentEditing::Search(_) => SetCursorStyle::SteadyBar,
+        _ => SetCursorStyle::SteadyBlock,
+    }
+}
 
+fn inside(area: Rect, row: usize, column: usize) -> Option<(u16, u16)> {
+    (row < area.height as usize && column < area.width as usize)
+        .then(|| (area.x + column as u16, area.y + row as u16))
 }
 
-fn into_spans<'a>(
-    line: &'a str, 
-    cursor_index: Option<usize>, 
-) -> Line {
-    let mut formated_line = Vec::new();
-    let mut found = false;
-    for (i, c) in line.chars().enumerate() {
-        if let Some(index) = cursor_index {
-            if index == i && !c.is_ascii_whitespace() {
-                formated_line.push(Span::styled(c.to_string(), Style::default().fg(Color::Rgb(183, 65, 14)).bg(Color::LightCyan)));
-                found = true;
-            } else if index == i {
-                formated_line.push(Span::styled(c.to_string(), Style::default().bg(Color::Rgb(183, 65, 14))));
-                found = true;
+fn file_view(app: &App, index: usize, window: Option<&Window>, focused: bool, frame: &mut Frame, area: Rect) -> Option<(u16, u16)> {
+    let file = &app.files[index];
+    let colors = &app.colors;
+    let local = Some(&file.local_options);
+    let cursor = if let Some(large) = &file.large {
+        large_view(app, file, large, frame, area)
+    } else if let Some(hex) = &file.hex {
+        hex_view(hex, colors, frame, area)
+    } else if let Some(explorer) = &file.explorer {
+        explorer_view(explorer, colors, frame, area)
+    } else if let Some((current_text, cursor_index, index)) = file.lines() {
+        let cursor_index = if focused { cursor_index } else { window.map_or(cursor_index, |window| index.index(&current_text, window.cursor)) };
+        let wrap = app.options.bool(local, "wrap");
+        let line_count = index.count();
+        let gutter = gutter_width(app, file, line_count);
+        let sub_layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length((gutter.0 + gutter.1 + gutter.2) as u16), Constraint::Fill(1)])
+            .split(area);
+        let (cursor_line, cursor_column) = index.position(&current_text, cursor_index);
+        let line = index.line(&current_text, cursor_line);
+        let cursor_byte = line.char_indices().nth(cursor_column).map_or(line.len(), |(i, _)| i);
+        let layout = WrapLayout {
+            width: sub_layout[1].width as usize,
+            linebreak: app.options.bool(None, "linebreak"),
+            showbreak: app.options.string(None, "showbreak"),
+            chars: app.char_display(local),
+        };
+        let mut viewport = window.map(|window| window.viewport.get()).unwrap_or_default();
+        viewport.height = sub_layout[1].height as usize;
+        viewport.width = sub_layout[1].width as usize;
+        let scrolloff = app.options.number(local, "scrolloff");
+        let (cursor_row, cursor_x) = if wrap {
+            let (row, x) = layout.locate(line, &layout.rows(line), cursor_byte);
+            viewport.scroll_to_row(cursor_line, row, scrolloff, |num| layout.rows(index.line(&current_text, num)).len());
+            (row, x)
+        } else {
+            let x = layout.chars.column(line, cursor_byte);
+            viewport.scroll_to(cursor_line, x, scrolloff, app.options.number(local, "sidescrolloff"));
+            (0, x)
+        };
+        if let Some(window) = window {
+            window.viewport.set(viewport);
+        }
+        let mut rows = vec![];
+        for num in viewport.top..line_count {
+            if rows.len() >= viewport.height {
+                break;
+            }
+            let line = index.line(&current_text, num);
+            if wrap {
+                rows.extend(layout.rows(line).into_iter().map(|range| (num, range)));
             } else {
-                formated_line.push(Span::styled(c.to_string(), Style::default().fg(Color::LightCyan)));
+                rows.push((num, 0..line.len()));
             }
-        } else {
-            formated_line.push(Span::styled(c.to_string(), Style::default().fg(Color::LightCyan)));
         }
+        rows.truncate(viewport.height);
+        let syntax = app.options.bool(local, "syntax") && file.name.ends_with(".rs");
+        let mut cache = window.map(|window| window.lines.borrow_mut());
+        let cursorline = focused && app.options.bool(local, "cursorline");
+        let mut lines = vec![];
+        for (num, range) in &rows {
+            let raw = index.line(&current_text, *num);
+            let row = if syntax {
+                let styled = match cache.as_mut() {
+                    Some(cache) => cache.get(raw, || highlight(raw, colors)),
+                    None => highlight(raw, colors),
+                };
+                slice_line(&styled, raw, range.clone())
+            } else {
+                into_spans(&raw[range.clone()], colors)
+            };
+            let last = range.end == raw.len();
+            let mut line = layout.chars.render(raw, &row, range.start, last, colors.get(Group::SpecialKey), colors.get(Group::NonText));
+            if range.start > 0 && !layout.showbreak.is_empty() {
+                line.spans.insert(0, Span::styled(layout.showbreak, colors.get(Group::NonText)));
+            }
+            if cursorline && *num == cursor_line {
+                line.style = line.style.patch(colors.get(Group::CursorLine));
+            }
+            lines.push(line);
+        }
+        if let Some(cache) = cache.as_mut() {
+            cache.end_frame();
+        }
+        let gutter_rows = rows.iter().map(|(num, range)| (*num, range.start == 0));
+        frame.render_widget(Text::from(gutter_lines(app, file, gutter_rows, cursor_line, gutter)), sub_layout[0]);
+        frame.render_widget(Paragraph::new(lines).scroll((0, viewport.left as u16)), sub_layout[1]);
+        let row = rows.iter().position(|(num, _)| *num == cursor_line).map_or(rows.len(), |first| first + cursor_row);
+        inside(sub_layout[1], row, cursor_x.saturating_sub(viewport.left))
+    } else {
+        None
+    };
+    cursor.filter(|_| focused)
+}
+
+fn large_view(app: &App, file: &File, large: &LargeFile, frame: &mut Frame, area: Rect) -> Option<(u16, u16)> {
+    let local = Some(&file.local_options);
+    let height = area.height as usize;
+    let scrolloff = app.options.number(local, "scrolloff").min(height.saturating_sub(1) / 2);
+    let mut top = large.top.get();
+    if large.line < top + scrolloff {
+        top = large.line.saturating_sub(scrolloff);
+    } else if large.line + scrolloff >= top + height {
+        top = large.line + scrolloff + 1 - height.max(1);
+    }
+    large.top.set(top);
+    let gutter = gutter_width(app, file, large.line_count());
+    let sub_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length((gutter.0 + gutter.1 + gutter.2) as u16), Constraint::Fill(1)])
+        .split(area);
+    let width = sub_layout[1].width as usize;
+    let chars = app.char_display(local);
+    let current = large.line(large.line).unwrap_or_default();
+    let cursor_x = chars.column(&current, large.column.min(current.len()));
+    let mut left = large.left.get();
+    if cursor_x < left {
+        left = cursor_x;
+    } else if cursor_x >= left + width {
+        left = cursor_x + 1 - width;
+    }
+    large.left.set(left);
+    let visible: Vec<_> = (top..top + height).map_while(|num| large.line(num)).collect();
+    let lines: Vec<Line> = visible
+        .iter()
+        .map(|line| {
+            let (start, end) = (chars.byte_at(line, left), chars.byte_at(line, left + width));
+            let row = into_spans(&line[start..end], &app.colors);
+            chars.render(line, &row, start, end == line.len(), app.colors.get(Group::SpecialKey), app.colors.get(Group::NonText))
+        })
+        .collect();
+    let numbers = gutter_lines(app, file, (top..top + visible.len()).map(|num| (num, true)), large.line, gutter);
+    frame.render_widget(Text::from(numbers), sub_layout[0]);
+    frame.render_widget(Text::from(lines), sub_layout[1]);
+    let start = chars.column(&current, chars.byte_at(&current, left));
+    inside(sub_layout[1], large.line - top, cursor_x.saturating_sub(start))
+}
+
+fn explorer_view(explorer: &Explorer, colors: &Colorscheme, frame: &mut Frame, area: Rect) -> Option<(u16, u16)> {
+    let height = (area.height as usize).saturating_sub(2);
+    let mut top = explorer.top.get();
+    if explorer.selected < top {
+        top = explorer.selected;
+    } else if explorer.selected >= top + height {
+        top = explorer.selected + 1 - height.max(1);
+    }
+    explorer.top.set(top);
+    let mut lines = vec![
+        Line::from(Span::styled(format!("{}/", explorer.dir.display()), colors.get(Group::Directory))),
+        Line::from(Span::styled(
+            format!("Enter: open  -: parent  %: new file  d: new directory  R: rename / move  D: delete  .: {} hidden files", if explorer.show_hidden { "hide" } else { "show" }),
+            colors.get(Group::Comment),
+        )),
+    ];
+    for (i, entry) in explorer.entries.iter().enumerate().skip(top).take(height) {
+        let suffix = match entry.kind {
+            EntryKind::Directory | EntryKind::Parent => "/",
+            EntryKind::Executable => "*",
+            EntryKind::Symlink => "@",
+            EntryKind::File => "",
+        };
+        let style = if i == explorer.selected { colors.get(Group::PmenuSel) } else { colors.get(Group::Normal) };
+        lines.push(Line::from(vec![
+            Span::raw(format!("{} ", entry.kind.icon())),
+            Span::styled(format!("{}{}", entry.name, suffix), style),
+        ]));
+    }
+    frame.render_widget(Text::from(lines), area);
+    let icon = explorer.entries.get(explorer.selected).map_or(0, |entry| entry.kind.icon().width() + 1);
+    inside(area, 2 + explorer.selected - top, icon)
+}
+
+fn hex_view(hex: &HexView, colors: &Colorscheme, frame: &mut Frame, area: Rect) -> Option<(u16, u16)> {
+    let height = area.height as usize;
+    let rows = hex.bytes.len() / ROW + 1;
+    let cursor_row = hex.cursor / ROW;
+    let mut top = hex.top.get();
+    if cursor_row < top {
+        top = cursor_row;
+    } else if cursor_row >= top + height {
+        top = cursor_row + 1 - height.max(1);
+    }
+    hex.top.set(top);
+    let normal = colors.get(Group::Normal);
+    let frame_style = colors.get(Group::LineNr);
+    let selected = colors.get(Group::PmenuSel);
+    let mut lines = vec![];
+    for row in top..rows.min(top + height) {
+        let start = row * ROW;
+        let mut spans = vec![Span::styled(format!("{:08x}  ", start), frame_style)];
+        let mut ascii = vec![Span::styled(" |", frame_style)];
+        for i in start..start + ROW {
+            let style = if i == hex.cursor { selected } else { normal };
+            match hex.bytes.get(i) {
+                Some(byte) => {
+                    spans.push(Span::styled(format!("{:02x}", byte), normal));
+                    let c = if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' };
+                    ascii.push(Span::styled(c.to_string(), style));
+                },
+                None => {
+                    spans.push(Span::styled("  ", normal));
+                    ascii.push(Span::styled(" ", style));
+                },
+            }
+            spans.push(Span::raw(if i % ROW == ROW / 2 - 1 { "  " } else { " " }));
+        }
+        ascii.push(Span::styled("|", frame_style));
+        spans.extend(ascii);
+        lines.push(Line::from(spans));
     }
-    if !found && cursor_index.is_some() {
-        formated_line.push(Span::styled(" ", Style::default().bg(Color::Rgb(183, 65, 14))));
+    frame.render_widget(Text::from(lines), area);
+    let column = hex.cursor % ROW;
+    let x = 10 + column * 3 + usize::from(column >= ROW / 2) + usize::from(hex.low_nibble);
+    inside(area, cursor_row - top, x)
+}
+
+fn message_line<'a>(message: &'a crate::app::Message, colors: &Colorscheme) -> Line<'a> {
+    if message.error {
+        Line::from(Span::styled(message.text.as_str(), colors.get(Group::ErrorMsg)))
+    } else {
+        Line::from(Span::styled(message.text.as_str(), colors.get(Group::Message)))
     }
-    Line::from(formated_line)
+}
+
+fn gutter_width(app: &App, file: &File, lines: usize) -> (usize, usize, usize) {
+    let local = Some(&file.local_options);
+    let folds = app.options.number(None, "foldcolumn").min(12);
+    let signs = match app.options.string(None, "signcolumn") {
+        "yes" => 2,
+        "auto" if !file.signs.is_empty() => 2,
+        _ => 0,
+    };
+    let numbers = if app.options.bool(local, "number") || app.options.bool(local, "relativenumber") {
+        (lines.max(1).to_string().len() + 1).max(app.options.number(None, "numberwidth"))
+    } else {
+        0
+    };
+    (folds, signs, numbers)
+}
+
+fn gutter_lines<'a>(
+    app: &App,
+    file: &File,
+    rows: impl Iterator<Item = (usize, bool)>,
+    cursor_line: usize,
+    (folds, signs, numbers): (usize, usize, usize),
+) -> Vec<Line<'a>> {
+    let local = Some(&file.local_options);
+    let number = app.options.bool(local, "number");
+    let relative = app.options.bool(local, "relativenumber");
+    let colors = &app.colors;
+    let mut gutter = vec![];
+    for (num, first) in rows {
+        let mut spans = vec![];
+        if folds > 0 {
+            spans.push(Span::styled(" ".repeat(folds), colors.get(Group::FoldColumn)));
+        }
+        if signs > 0 {
+            match file.signs.at(num).filter(|_| first) {
+                Some(sign) => spans.push(Span::styled(format!("{:<2}", sign.text), colors.get(sign.group))),
+                None => spans.push(Span::styled("  ", colors.get(Group::SignColumn))),
+            }
+        }
+        if numbers > 0 && first {
+            let width = numbers - 1;
+            let label = if relative && num != cursor_line {
+                format!("{:>width$} ", num.abs_diff(cursor_line))
+            } else if relative && !number {
+                format!("{:>width$} ", 0)
+            } else if relative {
+                format!("{:<width$} ", num + 1)
+            } else {
+                format!("{:>width$} ", num + 1)
+            };
+            let group = if num == cursor_line { Group::CursorLineNr } else { Group::LineNr };
+            spans.push(Span::styled(label, colors.get(group)));
+        }
+        gutter.push(Line::from(spans));
+    }
+    gutter
+}
+
+fn slice_line<'a>(line: &Line<'a>, raw: &'a str, range: Range<usize>) -> Line<'a> {
+    let mut spans = vec![];
+    let mut offset = 0;
+    for span in &line.spans {
+        let end = offset + span.content.len();
+        let (from, to) = (range.start.max(offset), range.end.min(end));
+        if from < to {
+            spans.push(Span::styled(span.content[from - offset..to - offset].to_owned(), span.style));
+        }
+        offset = end;
+    }
+    if offset < range.end {
+        spans.push(Span::raw(&raw[offset.max(range.start)..range.end]));
+    }
+    Line::from(spans)
+}
+
+fn into_spans<'a>(line: &'a str, colors: &Colorscheme) -> Line<'a> {
+    Line::from(Span::styled(line, colors.get(Group::Normal)))
 }
@@ -1,8 +1,11 @@
 use std::{
     rc::Rc,
-    cell::RefCell
+    cell::{Ref, RefCell},
+    sync::atomic::{AtomicU64, Ordering},
 };
 
+static VERSION: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Clone)]
 pub struct Cursor {
     index: usize,
@@ -17,6 +20,10 @@ impl Cursor {
         }
     }
 
+    pub fn set(&mut self, index: usize) {
+        self.index = index.min(self.max);
+    }
+
     pub fn move_left(&mut self) {
         if self.index > 0 {
             self.index -= 1;
@@ -108,7 +115,8 @@ impl UndoNode {
 
 pub struct UndoTree {
     pub current: Option<Rc<RefCell<UndoNode>>>,
-    current_child: Option<Rc<RefCell<UndoNode>>>
+    current_child: Option<Rc<RefCell<UndoNode>>>,
+    version: u64,
 }
 
 impl UndoTree {
@@ -116,10 +124,20 @@ impl UndoTree {
         UndoTree {
             current: None,
             current_child: None,
+            version: VERSION.fetch_add(1, Ordering::Relaxed),
         }
     }
 
+    fn changed(&mut self) {
+        self.version = VERSION.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
     pub fn add_node(&mut self, text: String, cursor: Cursor) {
+        self.changed();
         let node = Rc::new(RefCell::new(UndoNode::new(text, self.current.clone(), cursor)));
         if let Some(current) = &mut self.current {
             current.borrow_mut().child = Some(node.clone());
@@ -130,7 +148,32 @@ impl UndoTree {
         }
     }
 
+    pub fn replace_text(&mut self, text: String, cursor_index: usize) {
+        let len = text.len();
+        self.add_node(text, Cursor::new(cursor_index.min(len), len));
+    }
+
+    pub fn squash(&mut self, checkpoint: Option<Rc<RefCell<UndoNode>>>) {
+        let current = match &self.current {
+            Some(current) => current.clone(),
+            None => return,
+        };
+        if let Some(node) = &checkpoint && Rc::ptr_eq(node, &current) {
+            return;
+        }
+        let (text, cursor) = (current.borrow().text.clone(), current.borrow().cursor.clone());
+        self.changed();
+        self.current = checkpoint;
+        self.current_child = None;
+        if let Some(node) = &self.current && node.borrow().text == text {
+            node.borrow_mut().cursor = cursor;
+            return;
+        }
+        self.add_node(text, cursor);
+    }
+
     pub fn undo(&mut self) {
+        self.changed();
         if self.current.is_some() {
             self.current_child = self.current.clone();
             let parent = self.current.as_ref().unwrap().borrow().parent.clone();
@@ -139,6 +182,7 @@ impl UndoTree {
     }
 
     pub fn redo(&mut self) {
+        self.changed();
         if let Some(node) = &self.current_child {
             self.current = Some(node.clone());
         }
@@ -155,7 +199,14 @@ impl UndoTree {
         }
     }
 
+    pub fn current_text(&self) -> Option<(Ref<'_, str>, usize)> {
+        let node = self.current.as_ref()?;
+        let cursor = node.borrow().cursor.index;
+        Some((Ref::map(node.borrow(), |node| node.text.as_str()), cursor))
+    }
+
     pub fn del_char(&mut self) {
+        self.changed();
         if let Some(node) = &mut self.current {
             let index = node.borrow().cursor.index;
             if index as i32 - 1 >= 0 {
@@ -167,6 +218,7 @@ impl UndoTree {
     }
 
     pub fn add_char(&mut self, c: char) {
+        self.changed();
         if let Some(node) = &mut self.current {
             let cursor_index = node.borrow().cursor.index;
             node.borrow_mut().text.insert(cursor_index, c);
@@ -177,6 +229,18 @@ impl UndoTree {
         } 
     }
 
+    pub fn replace_char(&mut self, c: char) {
+        if let Some(node) = &self.current {
+            let mut node = node.borrow_mut();
+            let index = node.cursor.index;
+            if node.text[index..].starts_with(|next| next != '\n') {
+                node.text.remove(index);
+                node.cursor.max -= 1;
+            }
+        }
+        self.add_char(c);
+    }
+
     pub fn add_newspace(&mut self) {
         self.add_char('\n');
         let mut text = String::new();
@@ -188,6 +252,12 @@ impl UndoTree {
         self.add_node(text, cursor);
     }
 
+    pub fn set_cursor(&mut self, index: usize) {
+        if let Some(node) = &mut self.current {
+            node.borrow_mut().cursor.set(index);
+        }
+    }
+
     pub fn move_cursor_up(&mut self) {
         if let Some(node) = &mut self.current {
             let text = node.borrow().text.clone();
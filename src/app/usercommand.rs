@@ -0,0 +1,213 @@
+use crate::app::{excommand::{line_of, parse_range, Lines}, App, CurrentScreenMode};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Nargs {
+    Zero,
+    One,
+    Any,
+    Optional,
+    AtLeastOne,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RangeKind {
+    None,
+    Line,
+    Whole,
+}
+
+pub struct UserCommand {
+    pub name: String,
+    pub nargs: Nargs,
+    pub range: RangeKind,
+    pub replacement: String,
+}
+
+impl UserCommand {
+    pub fn parse(args: &str) -> Result<(UserCommand, bool), String> {
+        let mut nargs = Nargs::Zero;
+        let mut range = RangeKind::None;
+        let mut rest = args.trim_start();
+        let bang = rest.starts_with('!');
+        if bang {
+            rest = rest[1..].trim_start();
+        }
+        while rest.starts_with('-') {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            match &rest[..end] {
+                "-nargs=0" => nargs = Nargs::Zero,
+                "-nargs=1" => nargs = Nargs::One,
+                "-nargs=*" => nargs = Nargs::Any,
+                "-nargs=?" => nargs = Nargs::Optional,
+                "-nargs=+" => nargs = Nargs::AtLeastOne,
+                "-range" => range = RangeKind::Line,
+                "-range=%" => range = RangeKind::Whole,
+                attribute => return Err(format!("E181: Invalid attribute: {}", attribute)),
+            }
+            rest = rest[end..].trim_start();
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let name = &rest[..end];
+        if !name.starts_with(|c: char| c.is_ascii_uppercase()) || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err("E183: User defined commands must start with an uppercase letter".to_owned());
+        }
+        let replacement = rest[end..].trim_start();
+        if replacement.is_empty() {
+            return Err("E471: Argument required".to_owned());
+        }
+        Ok((UserCommand {
+            name: name.to_owned(),
+            nargs,
+            range,
+            replacement: replacement.to_owned(),
+        }, bang))
+    }
+
+    fn describe(&self) -> String {
+        let nargs = match self.nargs {
+            Nargs::Zero => "0",
+            Nargs::One => "1",
+            Nargs::Any => "*",
+            Nargs::Optional => "?",
+            Nargs::AtLeastOne => "+",
+        };
+        let range = match self.range {
+            RangeKind::None => "",
+            RangeKind::Line => ".",
+            RangeKind::Whole => "%",
+        };
+        format!("{:<12} {:<5} {:<5} {}", self.name, nargs, range, self.replacement)
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl App {
+    pub fn define_command(&mut self, args: &str) {
+        if args.trim().is_empty() {
+            self.info(format!("{:<12} {:<5} {:<5} Definition", "Name", "Args", "Range"));
+            let descriptions: Vec<String> = self.user_commands.iter().map(|command| command.describe()).collect();
+            for description in descriptions {
+                self.info(description);
+            }
+            self.show_messages = true;
+            return;
+        }
+        match UserCommand::parse(args) {
+            Ok((command, bang)) => match self.user_commands.iter().position(|c| c.name == command.name) {
+                Some(i) if bang => self.user_commands[i] = command,
+                Some(_) => self.error(format!("E174: Command already exists: add ! to replace it: {}", command.name)),
+                None => self.user_commands.push(command),
+            },
+            Err(error) => self.error(error),
+        }
+    }
+
+    pub fn delete_command(&mut self, name: &str) {
+        match self.user_commands.iter().position(|command| command.name == name) {
+            Some(i) => {
+                self.user_commands.remove(i);
+            },
+            None => self.error(format!("E184: No such user-defined command: {}", name)),
+        }
+    }
+
+    pub fn define_alias(&mut self, args: &str) {
+        let mut words = args.split_ascii_whitespace();
+        match (words.next(), words.remainder()) {
+            (None, _) => {
+                let mut aliases: Vec<String> = self.aliases.iter().map(|(lhs, rhs)| format!("{:<12} {}", lhs, rhs)).collect();
+                aliases.sort();
+                for alias in aliases {
+                    self.info(alias);
+                }
+                self.show_messages = true;
+            },
+            (Some(lhs), Some(rhs)) => {
+                self.aliases.insert(lhs.to_owned(), rhs.trim().to_owned());
+            },
+            (Some(lhs), None) => match self.aliases.get(lhs) {
+                Some(rhs) => self.info(format!("{:<12} {}", lhs, rhs)),
+                None => self.error(format!("E184: No such alias: {}", lhs)),
+            },
+        }
+    }
+
+    pub fn expand_user_command(&self, command: &str) -> Result<String, String> {
+        let mut command = command.to_owned();
+        let mut used_aliases = vec![];
+        for _ in 0..10 {
+            match self.expand_once(&command, &mut used_aliases)? {
+                Some(expanded) => command = expanded,
+                None => return Ok(command),
+            }
+        }
+        Err("E169: Command too recursive".to_owned())
+    }
+
+    fn expand_once(&self, command: &str, used_aliases: &mut Vec<String>) -> Result<Option<String>, String> {
+        let (lines, current) = match self.current_screen {
+            CurrentScreenMode::File(index) => match self.files[index].undo_tree.show_current_node() {
+                Some((text, cursor)) => (Lines::new(&text).lines.len(), line_of(&text, cursor)),
+                None => (1, 0),
+            },
+            _ => (1, 0),
+        };
+        let command = command.trim_start();
+        let (range, rest) = parse_range(command, current, lines).unwrap_or((None, command));
+        let range_text = &command[..command.len() - rest.len()];
+        let rest = rest.trim_start();
+        let end = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+        let (name, args) = (&rest[..end], rest[end..].trim());
+        if name.is_empty() {
+            return Ok(None);
+        }
+        if let Some(rhs) = self.aliases.get(name) && !used_aliases.iter().any(|used| used == name) {
+            used_aliases.push(name.to_owned());
+            return Ok(Some(format!("{}{}{}", range_text, rhs, &rest[end..])));
+        }
+        let user_command = match self.user_commands.iter().find(|c| c.name == name) {
+            Some(user_command) => user_command,
+            None => return Ok(None),
+        };
+        let words: Vec<&str> = args.split_whitespace().collect();
+        let valid = match user_command.nargs {
+            Nargs::Zero => words.is_empty(),
+            Nargs::One => !args.is_empty(),
+            Nargs::Any => true,
+            Nargs::Optional => words.len() <= 1,
+            Nargs::AtLeastOne => !words.is_empty(),
+        };
+        if !valid {
+            return Err(if words.is_empty() { "E471: Argument required".to_owned() } else { "E488: Trailing characters".to_owned() });
+        }
+        let (line1, line2, count) = match (range, user_command.range) {
+            (Some(_), RangeKind::None) => return Err("E481: No range allowed".to_owned()),
+            (Some((start, end)), _) => (start, end, if range_text.contains([',', '%']) { 2 } else { 1 }),
+            (None, RangeKind::Whole) => (1, lines, 0),
+            (None, _) => (current + 1, current + 1, 0),
+        };
+        let mut expanded = String::new();
+        let mut replacement = user_command.replacement.as_str();
+        while let Some(start) = replacement.find('<') {
+            expanded.push_str(&replacement[..start]);
+            let token_end = replacement[start..].find('>').map(|i| start + i + 1);
+            let token = token_end.map(|end| &replacement[start..end]).unwrap_or("<");
+            match token {
+                "<args>" => expanded.push_str(args),
+                "<q-args>" => expanded.push_str(&quote(args)),
+                "<f-args>" => expanded.push_str(&words.iter().map(|word| quote(word)).collect::<Vec<_>>().join(", ")),
+                "<line1>" => expanded.push_str(&line1.to_string()),
+                "<line2>" => expanded.push_str(&line2.to_string()),
+                "<range>" => expanded.push_str(&count.to_string()),
+                "<lt>" => expanded.push('<'),
+                token => expanded.push_str(token),
+            }
+            replacement = &replacement[start + token.len()..];
+        }
+        expanded.push_str(replacement);
+        Ok(Some(expanded))
+    }
+}
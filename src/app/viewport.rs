@@ -0,0 +1,269 @@
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::app::{display::{CharDisplay, ListChars}, options::LocalOptions, render::LineIndex, App};
+
+const BREAKAT: &str = " \t!@*-+;:,./?";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scroll {
+    LineDown,
+    LineUp,
+    HalfDown,
+    HalfUp,
+    PageDown,
+    PageUp,
+    Center,
+    Top,
+    Bottom,
+    ColumnLeft,
+    ColumnRight,
+    HalfLeft,
+    HalfRight,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Viewport {
+    pub top: usize,
+    pub left: usize,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl Viewport {
+    pub fn scroll_to(&mut self, line: usize, column: usize, scrolloff: usize, sidescrolloff: usize) {
+        let scrolloff = scrolloff.min(self.height.saturating_sub(1) / 2);
+        if line < self.top + scrolloff {
+            self.top = line.saturating_sub(scrolloff);
+        } else if line + scrolloff >= self.top + self.height {
+            self.top = line + scrolloff + 1 - self.height.max(1);
+        }
+        let sidescrolloff = sidescrolloff.min(self.width.saturating_sub(1) / 2);
+        if column < self.left + sidescrolloff {
+            self.left = column.saturating_sub(sidescrolloff);
+        } else if column + sidescrolloff >= self.left + self.width {
+            self.left = column + sidescrolloff + 1 - self.width.max(1);
+        }
+    }
+
+    pub fn scroll_to_row(&mut self, line: usize, row: usize, scrolloff: usize, rows: impl Fn(usize) -> usize) {
+        let scrolloff = scrolloff.min(self.height.saturating_sub(1) / 2);
+        self.left = 0;
+        if line < self.top + scrolloff {
+            self.top = line.saturating_sub(scrolloff);
+        }
+        self.top = self.top.max(line.saturating_sub(self.height));
+        while self.top < line && (self.top..line).map(&rows).sum::<usize>() + row + scrolloff >= self.height {
+            self.top += 1;
+        }
+    }
+}
+
+pub struct WrapLayout<'a> {
+    pub width: usize,
+    pub linebreak: bool,
+    pub showbreak: &'a str,
+    pub chars: CharDisplay,
+}
+
+impl WrapLayout<'_> {
+    fn available(&self, row: usize) -> usize {
+        if row == 0 { self.width } else { self.width.saturating_sub(self.showbreak.width()) }.max(1)
+    }
+
+    pub fn rows(&self, line: &str) -> Vec<Range<usize>> {
+        let mut rows = vec![];
+        let mut start = 0;
+        let mut column = 0;
+        loop {
+            let available = self.available(rows.len());
+            let mut used = 0;
+            let mut end = line.len();
+            let mut last_break = None;
+            for (i, c) in line[start..].char_indices() {
+                let width = self.chars.width(c, column + used);
+                if used + width > available && i > 0 {
+                    end = start + i;
+                    break;
+                }
+                used += width;
+                if BREAKAT.contains(c) {
+                    last_break = Some(start + i + c.len_utf8());
+                }
+            }
+            if end == line.len() {
+                rows.push(start..end);
+                return rows;
+            }
+            if self.linebreak && let Some(at) = last_break {
+                end = at;
+            }
+            rows.push(start..end);
+            column = self.chars.column(line, end);
+            start = end;
+        }
+    }
+
+    pub fn locate(&self, line: &str, rows: &[Range<usize>], byte: usize) -> (usize, usize) {
+        let row = rows.iter().rposition(|range| range.start <= byte).unwrap_or(0);
+        let prefix = if row == 0 { 0 } else { self.showbreak.width() };
+        let column = prefix + self.chars.column(line, byte) - self.chars.column(line, rows[row].start);
+        if column >= self.width && row + 1 == rows.len() {
+            (row + 1, self.showbreak.width())
+        } else {
+            (row, column)
+        }
+    }
+
+    pub fn byte_at(&self, line: &str, rows: &[Range<usize>], row: usize, column: usize) -> usize {
+        let range = rows[row].clone();
+        let mut column = if row == 0 { column } else { column.saturating_sub(self.showbreak.width()) };
+        let mut line_column = self.chars.column(line, range.start);
+        let mut chars = line[range.clone()].char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            let width = self.chars.width(c, line_column);
+            if column < width || (chars.peek().is_none() && row + 1 < rows.len()) {
+                return range.start + i;
+            }
+            column -= width;
+            line_column += width;
+        }
+        range.end
+    }
+}
+
+fn cursor_at(text: &str, index: &LineIndex, line: usize, column: usize) -> usize {
+    let line = line.min(index.count().saturating_sub(1));
+    let content = index.line(text, line);
+    index.start(line) + content.char_indices().nth(column).map_or(content.len(), |(i, _)| i)
+}
+
+impl App {
+    fn scrolloff(&self, height: usize) -> usize {
+        self.options.number(self.local_options(), "scrolloff").min(height.saturating_sub(1) / 2)
+    }
+
+    pub fn char_display(&self, local: Option<&LocalOptions>) -> CharDisplay {
+        CharDisplay {
+            tabstop: self.options.number(local, "tabstop"),
+            list: self.options.bool(None, "list").then(|| ListChars::parse(self.options.string(None, "listchars")).unwrap_or_default()),
+        }
+    }
+
+    fn wrap_layout(&self) -> WrapLayout<'_> {
+        WrapLayout {
+            width: self.windows[self.current_window].viewport.get().width,
+            linebreak: self.options.bool(None, "linebreak"),
+            showbreak: self.options.string(None, "showbreak"),
+            chars: self.char_display(self.local_options()),
+        }
+    }
+
+    pub fn display_line(&mut self, index: usize, down: bool) {
+        if !self.options.bool(self.local_options(), "wrap") {
+            if down {
+                self.files[index].undo_tree.move_cursor_down();
+            } else {
+                self.files[index].undo_tree.move_cursor_up();
+            }
+            return;
+        }
+        let cursor = {
+            let Some((text, cursor, lines)) = self.files[index].lines() else { return };
+            let layout = self.wrap_layout();
+            let (line, column) = lines.position(&text, cursor);
+            if line >= lines.count() {
+                return;
+            }
+            let content = lines.line(&text, line);
+            let byte = content.char_indices().nth(column).map_or(content.len(), |(i, _)| i);
+            let rows = layout.rows(content);
+            let (row, x) = layout.locate(content, &rows, byte);
+            let (target_line, target_row) = match down {
+                true if row + 1 < rows.len() => (line, row + 1),
+                true if line + 1 < lines.count() => (line + 1, 0),
+                false if row > 0 => (line, row.min(rows.len()) - 1),
+                false if line > 0 => (line - 1, layout.rows(lines.line(&text, line - 1)).len() - 1),
+                _ => return,
+            };
+            let target = lines.line(&text, target_line);
+            lines.start(target_line) + layout.byte_at(target, &layout.rows(target), target_row, x)
+        };
+        self.files[index].undo_tree.set_cursor(cursor);
+    }
+
+    fn scroll_horizontally(&mut self, index: usize, scroll: Scroll) {
+        if self.options.bool(self.local_options(), "wrap") {
+            return;
+        }
+        let mut viewport = self.windows[self.current_window].viewport.get();
+        let width = viewport.width.max(1);
+        let half = (width / 2).max(1);
+        viewport.left = match scroll {
+            Scroll::ColumnLeft => viewport.left.saturating_sub(1),
+            Scroll::ColumnRight => viewport.left + 1,
+            Scroll::HalfLeft => viewport.left.saturating_sub(half),
+            _ => viewport.left + half,
+        };
+        self.windows[self.current_window].viewport.set(viewport);
+        let cursor = {
+            let Some((text, cursor, lines)) = self.files[index].lines() else { return };
+            let (line, column) = lines.position(&text, cursor);
+            let content = lines.line(&text, line);
+            let chars = self.char_display(self.local_options());
+            let byte = content.char_indices().nth(column).map_or(content.len(), |(i, _)| i);
+            let display = chars.column(content, byte);
+            let sidescrolloff = self.options.number(self.local_options(), "sidescrolloff").min(width.saturating_sub(1) / 2);
+            let target = display.clamp(viewport.left + sidescrolloff, viewport.left + width - 1 - sidescrolloff);
+            if target == display {
+                return;
+            }
+            lines.start(line) + chars.byte_at(content, target)
+        };
+        self.files[index].undo_tree.set_cursor(cursor);
+    }
+
+    pub fn scroll(&mut self, index: usize, scroll: Scroll) {
+        if matches!(scroll, Scroll::ColumnLeft | Scroll::ColumnRight | Scroll::HalfLeft | Scroll::HalfRight) {
+            self.scroll_horizontally(index, scroll);
+            return;
+        }
+        let Some((text, cursor, lines)) = self.files[index].lines() else { return };
+        let mut viewport = self.windows[self.current_window].viewport.get();
+        let (line, column) = lines.position(&text, cursor);
+        let last = lines.newlines();
+        let height = viewport.height.max(1);
+        let scrolloff = self.scrolloff(height);
+        let half = (height / 2).max(1);
+        let page = height.saturating_sub(2).max(1);
+        let mut target = line;
+        match scroll {
+            Scroll::LineDown => viewport.top = (viewport.top + 1).min(last),
+            Scroll::LineUp => viewport.top = viewport.top.saturating_sub(1),
+            Scroll::HalfDown => {
+                viewport.top = (viewport.top + half).min(last);
+                target = (line + half).min(last);
+            },
+            Scroll::HalfUp => {
+                viewport.top = viewport.top.saturating_sub(half);
+                target = line.saturating_sub(half);
+            },
+            Scroll::PageDown => viewport.top = (viewport.top + page).min(last),
+            Scroll::PageUp => viewport.top = viewport.top.saturating_sub(page),
+            Scroll::Center => viewport.top = line.saturating_sub(height / 2),
+            Scroll::Top => viewport.top = line.saturating_sub(scrolloff),
+            Scroll::Bottom => viewport.top = (line + scrolloff + 1).saturating_sub(height),
+            _ => (),
+        }
+        let first_visible = if viewport.top == 0 { 0 } else { viewport.top + scrolloff };
+        let last_visible = if viewport.top + height > last { last } else { (viewport.top + height - 1).saturating_sub(scrolloff) };
+        target = target.clamp(first_visible.min(last_visible), last_visible);
+        self.windows[self.current_window].viewport.set(viewport);
+        if target != line {
+            let cursor = cursor_at(&text, &lines, target, column);
+            drop((text, lines));
+            self.files[index].undo_tree.set_cursor(cursor);
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use std::cell::{Cell, RefCell};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+
+use crate::app::{render::LineCache, viewport::Viewport, App, CurrentEditing, CurrentScreenMode};
+
+pub const CTRL_W: char = '\u{17}';
+
+pub struct Window {
+    pub file: usize,
+    pub cursor: (usize, usize),
+    pub viewport: Cell<Viewport>,
+    pub area: Cell<Rect>,
+    pub lines: RefCell<LineCache>,
+}
+
+impl Window {
+    fn new(file: usize, cursor: (usize, usize), viewport: Viewport) -> Window {
+        Window {
+            file,
+            cursor,
+            viewport: Cell::new(viewport),
+            area: Cell::new(Rect::default()),
+            lines: RefCell::new(LineCache::default()),
+        }
+    }
+}
+
+pub enum WindowTree {
+    Leaf(usize),
+    Split(Direction, Vec<(WindowTree, u16)>),
+}
+
+impl WindowTree {
+    fn path(&self, window: usize) -> Option<Vec<usize>> {
+        match self {
+            WindowTree::Leaf(id) => (*id == window).then(Vec::new),
+            WindowTree::Split(_, children) => children.iter().enumerate().find_map(|(i, (child, _))| {
+                child.path(window).map(|mut path| {
+                    path.insert(0, i);
+                    path
+                })
+            }),
+        }
+    }
+
+    fn node(&mut self, path: &[usize]) -> &mut WindowTree {
+        match (self, path.split_first()) {
+            (WindowTree::Split(_, children), Some((&i, rest))) => children[i].0.node(rest),
+            (node, _) => node,
+        }
+    }
+
+    pub fn areas(&self, area: Rect, areas: &mut Vec<(usize, Rect)>) {
+        match self {
+            WindowTree::Leaf(id) => areas.push((*id, area)),
+            WindowTree::Split(direction, children) => {
+                let chunks = Layout::default()
+                    .direction(*direction)
+                    .constraints(children.iter().map(|(_, weight)| Constraint::Fill(*weight)))
+                    .split(area);
+                for ((child, _), chunk) in children.iter().zip(chunks.iter()) {
+                    child.areas(*chunk, areas);
+                }
+            },
+        }
+    }
+
+    fn split(&mut self, window: usize, new: usize, direction: Direction, size: u16) {
+        let Some(path) = self.path(window) else { return };
+        let first = (size / 2).max(1);
+        let second = size.saturating_sub(first).max(1);
+        if let Some((&i, parent)) = path.split_last()
+            && let WindowTree::Split(parent_direction, children) = self.node(parent)
+            && *parent_direction == direction {
+            children[i].1 = second;
+            children.insert(i, (WindowTree::Leaf(new), first));
+            return;
+        }
+        let node = self.node(&path);
+        *node = WindowTree::Split(direction, vec![(WindowTree::Leaf(new), first), (WindowTree::Leaf(window), second)]);
+    }
+
+    fn remove(&mut self, window: usize) {
+        if let Some(path) = self.path(window)
+            && let Some((&i, parent_path)) = path.split_last() {
+            let parent = self.node(parent_path);
+            if let WindowTree::Split(_, children) = parent {
+                children.remove(i);
+                if children.len() == 1 {
+                    let (child, _) = children.remove(0);
+                    *parent = child;
+                }
+            }
+        }
+        self.renumber(window);
+    }
+
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            WindowTree::Leaf(id) => if *id > removed {
+                *id -= 1;
+            },
+            WindowTree::Split(_, children) => for (child, _) in children {
+                child.renumber(removed);
+            },
+        }
+    }
+
+    fn measure(&mut self, windows: &[Window]) -> Rect {
+        match self {
+            WindowTree::Leaf(id) => windows[*id].area.get(),
+            WindowTree::Split(direction, children) => {
+                let mut area: Option<Rect> = None;
+                for (child, weight) in children {
+                    let child_area = child.measure(windows);
+                    if !child_area.is_empty() {
+                        *weight = if *direction == Direction::Vertical { child_area.height } else { child_area.width };
+                    }
+                    area = Some(area.map_or(child_area, |area| area.union(child_area)));
+                }
+                area.unwrap_or_default()
+            },
+        }
+    }
+
+    fn resize(&mut self, window: usize, direction: Direction, delta: i32) {
+        let Some(path) = self.path(window) else { return };
+        for depth in (0..path.len()).rev() {
+            if let WindowTree::Split(split_direction, children) = self.node(&path[..depth])
+                && *split_direction == direction {
+                let i = path[depth];
+                let sibling = if i + 1 < children.len() { i + 1 } else { i - 1 };
+                let delta = delta.clamp(1 - children[i].1 as i32, children[sibling].1 as i32 - 1);
+                children[i].1 = (children[i].1 as i32 + delta) as u16;
+                children[sibling].1 = (children[sibling].1 as i32 - delta) as u16;
+                return;
+            }
+        }
+    }
+
+    fn equalize(&mut self) {
+        if let WindowTree::Split(_, children) = self {
+            let size = children.iter().map(|(_, weight)| *weight).sum::<u16>() / children.len() as u16;
+            for (child, weight) in children {
+                *weight = size.max(1);
+                child.equalize();
+            }
+        }
+    }
+
+    fn rotate(&mut self, window: usize, downwards: bool) {
+        let Some(path) = self.path(window) else { return };
+        if let Some((_, parent)) = path.split_last()
+            && let WindowTree::Split(_, children) = self.node(parent) {
+            if downwards {
+                children.rotate_right(1);
+            } else {
+                children.rotate_left(1);
+            }
+        }
+    }
+}
+
+impl App {
+    pub fn sync_windows(&mut self) {
+        let CurrentScreenMode::File(index) = self.current_screen else {
+            self.windows.clear();
+            self.window_tree = WindowTree::Leaf(0);
+            self.current_window = 0;
+            return;
+        };
+        let cursor = self.file_cursor(index);
+        if self.windows.is_empty() {
+            self.windows.push(Window::new(index, cursor, Viewport::default()));
+            self.window_tree = WindowTree::Leaf(0);
+            self.current_window = 0;
+        }
+        let window = &mut self.windows[self.current_window];
+        if window.file != index {
+            window.file = index;
+            window.viewport.set(Viewport::default());
+        }
+        window.cursor = cursor;
+    }
+
+    fn file_cursor(&self, index: usize) -> (usize, usize) {
+        self.files[index].cursor_position().unwrap_or((0, 0))
+    }
+
+    pub fn focus_window(&mut self, window: usize) {
+        if window == self.current_window || window >= self.windows.len() {
+            return;
+        }
+        self.sync_windows();
+        self.enter_window(window);
+    }
+
+    fn enter_window(&mut self, window: usize) {
+        self.current_window = window;
+        let (file, position) = (self.windows[window].file, self.windows[window].cursor);
+        self.current_screen = CurrentScreenMode::File(file);
+        let cursor = self.files[file].lines().map(|(text, _, lines)| lines.index(&text, position));
+        if let Some(cursor) = cursor {
+            self.files[file].undo_tree.set_cursor(cursor);
+        }
+    }
+
+    pub fn split_window(&mut self, direction: Direction, name: Option<&str>) {
+        self.sync_windows();
+        if self.windows.is_empty() {
+            if let Some(name) = name {
+                self.open_path(name.to_owned(), None);
+            }
+            return;
+        }
+        let current = &self.windows[self.current_window];
+        let area = current.area.get();
+        let size = if direction == Direction::Vertical { area.height } else { area.width };
+        let window = Window::new(current.file, current.cursor, current.viewport.get());
+        self.windows.push(window);
+        let new = self.windows.len() - 1;
+        self.window_tree.split(self.current_window, new, direction, size);
+        self.current_window = new;
+        if let Some(name) = name
+            && !self.switch_to(name) {
+            self.open_path(name.to_owned(), None);
+        }
+        self.sync_windows();
+    }
+
+    pub fn close_window(&mut self, window: usize) -> Result<(), String> {
+        if self.windows.len() < 2 {
+            return Err("E444: Cannot close last window".to_owned());
+        }
+        self.sync_windows();
+        self.windows.remove(window);
+        self.window_tree.remove(window);
+        let current = if self.current_window > window || self.current_window == self.windows.len() {
+            self.current_window - 1
+        } else {
+            self.current_window
+        };
+        self.enter_window(current);
+        Ok(())
+    }
+
+    pub fn only_window(&mut self) {
+        self.sync_windows();
+        if self.windows.is_empty() {
+            return;
+        }
+        let window = self.windows.swap_remove(self.current_window);
+        self.windows = vec![window];
+        self.window_tree = WindowTree::Leaf(0);
+        self.current_window = 0;
+    }
+
+    pub fn remove_file_windows(&mut self, file: usize) -> bool {
+        let mut window = 0;
+        while window < self.windows.len() {
+            if self.windows[window].file == file && self.windows.len() > 1 {
+                self.windows.remove(window);
+                self.window_tree.remove(window);
+                if self.current_window > window || self.current_window == self.windows.len() {
+                    self.current_window -= 1;
+                }
+                continue;
+            }
+            if self.windows[window].file > file {
+                self.windows[window].file -= 1;
+            }
+            window += 1;
+        }
+        match self.windows.get_mut(self.current_window) {
+            Some(window) if window.file == file => {
+                window.file = usize::MAX;
+                false
+            },
+            Some(_) => {
+                self.enter_window(self.current_window);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn resize_window(&mut self, direction: Direction, delta: i32) {
+        if self.windows.is_empty() {
+            return;
+        }
+        self.window_tree.measure(&self.windows);
+        self.window_tree.resize(self.current_window, direction, delta);
+    }
+
+    pub fn resize_command(&mut self, direction: Direction, argument: &str) -> Result<(), String> {
+        let invalid = || format!("E475: Invalid argument: {}", argument);
+        let Some(window) = self.windows.get(self.current_window) else { return Ok(()) };
+        let area = window.area.get();
+        let size = if direction == Direction::Vertical { area.height } else { area.width } as i32;
+        let delta = match argument.chars().next() {
+            Some('+') => argument[1..].parse::<i32>().map_err(|_| invalid())?,
+            Some('-') => -argument[1..].parse::<i32>().map_err(|_| invalid())?,
+            Some(_) => argument.parse::<i32>().map_err(|_| invalid())? - size,
+            None => 0,
+        };
+        self.resize_window(direction, delta);
+        Ok(())
+    }
+
+    fn neighbour(&self, direction: KeyCode) -> Option<usize> {
+        let current = self.windows[self.current_window].area.get();
+        let overlaps = |a: (u16, u16), b: (u16, u16)| a.0 < b.1 && b.0 < a.1;
+        self.windows
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != self.current_window)
+            .filter_map(|(i, window)| {
+                let area = window.area.get();
+                let vertical = overlaps((area.x, area.right()), (current.x, current.right()));
+                let horizontal = overlaps((area.y, area.bottom()), (current.y, current.bottom()));
+                let distance = match direction {
+                    KeyCode::Char('h') | KeyCode::Left if horizontal && area.right() <= current.x => current.x - area.right(),
+                    KeyCode::Char('l') | KeyCode::Right if horizontal && area.x >= current.right() => area.x - current.right(),
+                    KeyCode::Char('k') | KeyCode::Up if vertical && area.bottom() <= current.y => current.y - area.bottom(),
+                    KeyCode::Char('j') | KeyCode::Down if vertical && area.y >= current.bottom() => area.y - current.bottom(),
+                    _ => return None,
+                };
+                Some((distance, i))
+            })
+            .min()
+            .map(|(_, i)| i)
+    }
+
+    pub fn window_key(&mut self, key: KeyEvent) {
+        self.current_editing = CurrentEditing::Selecting;
+        self.sync_windows();
+        let code = match key.code {
+            KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => KeyCode::Char(c.to_ascii_lowercase()),
+            code => code,
+        };
+        let count = self.windows.len();
+        let current = self.current_window;
+        match code {
+            KeyCode::Char('s' | 'S') => self.split_window(Direction::Vertical, None),
+            KeyCode::Char('v') => self.split_window(Direction::Horizontal, None),
+            KeyCode::Char('w') => self.focus_window((current + 1) % count),
+            KeyCode::Char('W') => self.focus_window((current + count - 1) % count),
+            KeyCode::Char('t') => self.focus_window(0),
+            KeyCode::Char('b') => self.focus_window(count - 1),
+            KeyCode::Char('h' | 'j' | 'k' | 'l') | KeyCode::Left | KeyCode::Down | KeyCode::Up | KeyCode::Right => {
+                if let Some(window) = self.neighbour(code) {
+                    self.focus_window(window);
+                }
+            },
+            KeyCode::Char('c') => if let Err(error) = self.close_window(current) {
+                self.error(error);
+            },
+            KeyCode::Char('q') => self.execute_command("q".to_owned()),
+            KeyCode::Char('o') => self.only_window(),
+            KeyCode::Char('r') => self.window_tree.rotate(current, true),
+            KeyCode::Char('R') => self.window_tree.rotate(current, false),
+            KeyCode::Char('+') => self.resize_window(Direction::Vertical, 1),
+            KeyCode::Char('-') => self.resize_window(Direction::Vertical, -1),
+            KeyCode::Char('>') => self.resize_window(Direction::Horizontal, 1),
+            KeyCode::Char('<') => self.resize_window(Direction::Horizontal, -1),
+            KeyCode::Char('=') => {
+                self.window_tree.measure(&self.windows);
+                self.window_tree.equalize();
+            },
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::layout::Direction;
+
+    use crate::app::{App, CurrentScreenMode, File};
+
+    #[test]
+    fn split_keeps_its_line_after_edits_elsewhere() {
+        let mut app = App::new();
+        let mut file = File::new("split".to_owned());
+        file.undo_tree.replace_text("first\nsecond\nthird\n".to_owned(), 0);
+        app.files.push(file);
+        app.current_screen = CurrentScreenMode::File(0);
+        app.files[0].undo_tree.set_cursor(16);
+        app.split_window(Direction::Vertical, None);
+        app.files[0].undo_tree.replace_text("1\nsecond\nthird\n".to_owned(), 0);
+        app.focus_window(0);
+        assert_eq!(app.files[0].cursor_position(), Some((2, 3)));
+        app.focus_window(1);
+        assert_eq!(app.files[0].cursor_position(), Some((0, 0)));
+    }
+}
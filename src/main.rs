@@ -3,7 +3,7 @@
 mod app;
 use clap::{command, Arg, ArgAction};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers}, execute, terminal::{
+    cursor::{SetCursorStyle, Show}, event::{self, DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture, Event}, execute, terminal::{
         disable_raw_mode, enable_raw_mode, EnterAlternateScreen,
         LeaveAlternateScreen,
     },
@@ -11,144 +11,139 @@ use crossterm::{
 use ratatui::{
     backend::Backend, prelude::{CrosstermBackend, Terminal},
 };
-use std::io::{stderr, Result};
+use std::{io::{stderr, Result}, panic::{self, AssertUnwindSafe}};
 use crate::app::*;
 
 fn init_terminal() -> Result<Terminal<CrosstermBackend<std::io::Stderr>>> {
     enable_raw_mode()?;
-    execute!(stderr(), EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stderr(), EnterAlternateScreen, EnableMouseCapture, EnableFocusChange)?;
     let mut terminal = Terminal::new(CrosstermBackend::new(stderr()))?;
     terminal.clear()?;
 
     Ok(terminal)
 }
 
-fn get_handle() -> Vec<(Result<std::fs::File>, String)> {
+fn restore_terminal() -> Result<()> {
+    disable_raw_mode()?;
+    execute!(
+        stderr(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableFocusChange,
+        SetCursorStyle::DefaultUserShape,
+        Show,
+    )
+}
+
+fn get_args() -> (Vec<String>, bool) {
     let matches = command!()
         .arg(Arg::new("file").action(ArgAction::Append))
+        .arg(Arg::new("recover").short('r').long("recover").action(ArgAction::SetTrue).help("List swap files, or recover the given files from their swap files"))
         .get_matches();
-    let args = matches
+    let files = matches
         .get_many::<String>("file")
         .unwrap_or_default()
-        .map(|v| v.as_str())
+        .cloned()
         .collect::<Vec<_>>();
-    let mut vec_files = vec![];
-    for result in args {
-        vec_files.push((std::fs::File::options()
-                        .read(true)
-                        .write(true)
-                        .create(true)
-                        .open(result), result.to_owned()));
+    (files, matches.get_flag("recover"))
+}
+
+fn list_swaps() {
+    let swaps = list_swap_files(std::path::Path::new("."));
+    if swaps.is_empty() {
+        println!("No swap files found in the current directory");
+        return;
+    }
+    println!("Swap files found in the current directory:");
+    for (i, (path, info)) in swaps.iter().enumerate() {
+        let process = if info.running() { " (still running)" } else { "" };
+        println!("{}. {}\n    file name: {}\n    process ID: {}{}", i + 1, path.display(), info.file, info.pid, process);
     }
-    vec_files
+    println!("Use rime -r FILE to recover FILE");
+}
+
+fn draw<B: Backend>(terminal: &mut Terminal<B>, app: &App) -> Result<()> {
+    terminal.draw(|frame| ui(app, frame))?;
+    execute!(stderr(), cursor_style(app))
 }
 
 fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     app: &mut App,
 )-> Result<bool> {
-    terminal.draw(|frame| ui(app, frame))?;
+    draw(terminal, app)?;
+    let mut typed = 0;
     loop {
-        if let Event::Key(key) = event::read()? {
-            if key.kind == event::KeyEventKind::Release {
+        if app.index_large_files() {
+            draw(terminal, app)?;
+            if !event::poll(std::time::Duration::ZERO)? {
                 continue;
             }
-            match app.current_screen {
-                CurrentScreenMode::Main => match key.code {
-                    KeyCode::Char('q') => return Ok(true),
-                    _ => (),
-                },
-                CurrentScreenMode::Config => {
-
-                },
-                CurrentScreenMode::File(index) => {
-                    match &app.current_editing {
-                        CurrentEditing::Page if key.kind == KeyEventKind::Press => 
-                            match key.code {
-                                KeyCode::Char('i') if key.modifiers == KeyModifiers::ALT => app.files[index].undo_tree.move_cursor_up(),
-                                KeyCode::Char('k') if key.modifiers == KeyModifiers::ALT => app.files[index].undo_tree.move_cursor_down(),
-                                KeyCode::Char('j') if key.modifiers == KeyModifiers::ALT => app.files[index].undo_tree.move_cursor_left(),
-                                KeyCode::Char('l') if key.modifiers == KeyModifiers::ALT => app.files[index].undo_tree.move_cursor_right(),
-                                KeyCode::Esc => app.current_editing = CurrentEditing::Selecting,
-                                KeyCode::Backspace => app.files[index].undo_tree.del_char(),
-                                KeyCode::Enter => app.files[index].undo_tree.add_newspace(),
-                                KeyCode::Char(c) => app.files[index].undo_tree.add_char(c),
-                                _ => (),
-                            },
-                        CurrentEditing::Command(string) => match key.code {
-                            KeyCode::Esc => app.current_editing = CurrentEditing::Selecting,
-                            KeyCode::Enter => app.execute_command(string.clone()),
-                            KeyCode::Backspace => app.current_editing = CurrentEditing::Command(string[..(if string.len() > 0 {string.len() - 1} else {0})].to_owned()),
-                            KeyCode::Char(c) => app.current_editing = CurrentEditing::Command(string.to_owned() + &c.to_string()),
-                            _ => (),
-                        },
-                        CurrentEditing::Selecting => match key.code {
-                            KeyCode::Char('i') if key.modifiers == KeyModifiers::ALT => app.files[index].undo_tree.move_cursor_up(),
-                            KeyCode::Char('k') if key.modifiers == KeyModifiers::ALT => app.files[index].undo_tree.move_cursor_down(),
-                            KeyCode::Char('j') if key.modifiers == KeyModifiers::ALT => app.files[index].undo_tree.move_cursor_left(),
-                            KeyCode::Char('l') if key.modifiers == KeyModifiers::ALT => app.files[index].undo_tree.move_cursor_right(),
-                            KeyCode::Char('i') => {
-                                let (text, cursor) = if let Some(node) = &app.files[index].undo_tree.current {
-                                    (node.borrow().text.clone(), node.borrow().cursor.clone())
-                                } else {("".to_owned(), Cursor::new(0, 1))};
-                                app.files[index].undo_tree.add_node(text, cursor);
-                                app.current_editing = CurrentEditing::Page;
-                            }
-                            KeyCode::Char('R') => app.files[index].undo_tree.redo(),
-                            KeyCode::Char('u') => app.files[index].undo_tree.undo(),
-                            KeyCode::Char(':') => app.current_editing = CurrentEditing::Command("".to_string()),
-                            _ => if let KeyCode::Char(c) = key.code {
-                                app.current_editing = CurrentEditing::Listening(c)
-                            },
-                        }
-                        CurrentEditing::Listening(c) => match c {
-                            'g' => match key.code {
-                                KeyCode::Char('t') => {
-                                    app.current_screen = if app.files.len() > index + 1 {
-                                        CurrentScreenMode::File(index + 1)
-                                    } else {
-                                        CurrentScreenMode::File(0)
-                                    };
-                                    app.current_editing = CurrentEditing::Selecting;
-                                },
-                                KeyCode::Char('T') => {
-                                    app.current_screen = if index as i32 - 1 > - 1 {
-                                        CurrentScreenMode::File(index - 1)
-                                    } else {
-                                        CurrentScreenMode::File(app.files.len() - 1)
-                                    };
-                                    app.current_editing = CurrentEditing::Selecting;
-                                },
-                                _ => app.current_editing = CurrentEditing::Selecting,
-                            },
-                            _ => app.current_editing = CurrentEditing::Selecting,
-                        }
-                        _ => (),
-                    }
+        }
+        let timeout = std::time::Duration::from_millis(app.options.number(None, "updatetime").max(1) as u64);
+        if !event::poll(timeout)? {
+            app.write_swap_files();
+            draw(terminal, app)?;
+            continue;
+        }
+        match event::read()? {
+            Event::Key(key) => {
+                if key.kind == event::KeyEventKind::Release {
+                    continue;
                 }
-            }
+                typed += 1;
+                let updatecount = app.options.number(None, "updatecount");
+                if updatecount > 0 && typed >= updatecount {
+                    app.write_swap_files();
+                    typed = 0;
+                }
+                if app.show_messages {
+                    app.show_messages = false;
+                    draw(terminal, app)?;
+                    continue;
+                }
+                if app.handle_key(key) {
+                    return Ok(true);
+                }
+            },
+            Event::FocusGained => app.check_time(),
+            _ => (),
         }
-        terminal.draw(|frame| ui(app, frame))?;
+        draw(terminal, app)?;
     }
 }
 
 fn main() -> Result<()>{
+    let (files, recover) = get_args();
+    if recover && files.is_empty() {
+        list_swaps();
+        return Ok(());
+    }
     let mut terminal = init_terminal()?;
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        default_hook(info);
+    }));
     let mut app = App::new();
-    let vec_files = get_handle();
-    for (file, name) in vec_files {
-        if file.is_ok() {
-            app.open_file(file.unwrap(), name);
-        }
+    app.recover = recover;
+    for name in files {
+        app.open_path(name, None);
+    }
+    app.recover = false;
+    let result = match panic::catch_unwind(AssertUnwindSafe(|| run_app(&mut terminal, &mut app))) {
+        Ok(result) => result,
+        Err(_) => Err(std::io::Error::other("internal error")),
+    };
+    match result {
+        Ok(true) => app.remove_swap_files(),
+        _ => app.write_swap_files(),
     }
-    let _res = run_app(&mut terminal, &mut app);
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture,
-    )?;
-    terminal.show_cursor()?;
+    restore_terminal()?;
+    if let Err(error) = result {
+        eprintln!("rime: {}, unsaved changes are kept in the swap files (rime -r FILE to recover them)", error);
+        return Err(error);
+    }
     Ok(())
 }